@@ -1,8 +1,16 @@
+use crate::command_buffer::OneTimeSubmitError;
+use crate::command_pool::CommandPool;
 use crate::device::Device;
-use ash::version::DeviceV1_0;
+use crate::memory::{MemAllocError, Memory, MemoryBuilder};
+use crate::queue::Queue;
+use ash::prelude::VkResult;
+use ash::version::{DeviceV1_0, DeviceV1_2};
 use ash::vk;
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 #[derive(Default)]
@@ -11,6 +19,7 @@ pub struct BufferBuilder {
     usage: vk::BufferUsageFlags,
     sharing_mode: vk::SharingMode,
     flags: vk::BufferCreateFlags,
+    next: Vec<Box<dyn vk::ExtendsBufferCreateInfo>>,
 }
 
 impl BufferBuilder {
@@ -34,26 +43,66 @@ impl BufferBuilder {
         self
     }
 
+    /// Adds extension struct to `p_next` chain of buffer create info.
+    /// Builder owns `ext` until buffer is built. `p_next` of `ext` is overwritten.
+    pub fn push_next<T: vk::ExtendsBufferCreateInfo + 'static>(mut self, ext: T) -> Self {
+        self.next.push(Box::new(ext));
+        self
+    }
+
+    /// For `CONCURRENT` sharing mode `queues_family_indices` must contain
+    /// at least two family indices without duplicates. For `EXCLUSIVE` one they are ignored.
     pub fn build(
-        self,
+        mut self,
         device: Device,
         queues_family_indices: &[u32],
     ) -> CreateBufferResult<Buffer> {
-        let create_info = vk::BufferCreateInfo {
+        let queues_family_indices = sharing_indices(self.sharing_mode, queues_family_indices)?;
+        let create_info = self.raw_create_info(queues_family_indices);
+        unsafe { Buffer::new(device, &create_info) }
+    }
+
+    /// Returned value borrows `self` and `queues_family_indices`.
+    fn raw_create_info(&mut self, queues_family_indices: &[u32]) -> vk::BufferCreateInfo {
+        vk::BufferCreateInfo {
+            p_next: unsafe { crate::link_p_next(&mut self.next) },
             flags: self.flags,
             size: self.size,
             usage: self.usage,
             sharing_mode: self.sharing_mode,
             queue_family_index_count: queues_family_indices.len() as u32,
-            p_queue_family_indices: queues_family_indices.as_ptr(),
+            p_queue_family_indices: if queues_family_indices.is_empty() {
+                ptr::null()
+            } else {
+                queues_family_indices.as_ptr()
+            },
             ..Default::default()
-        };
+        }
+    }
 
-        unsafe { Buffer::new(device, &create_info) }
+    /// Builds buffer, allocates memory of first type with `properties`, suitable for it,
+    /// and binds memory to buffer.
+    pub fn build_with_memory(
+        self,
+        device: Device,
+        queues_family_indices: &[u32],
+        properties: vk::MemoryPropertyFlags,
+    ) -> CreateBufferResult<(Buffer, Memory)> {
+        let buffer = self.build(device.clone(), queues_family_indices)?;
+
+        let requirements = buffer.memory_requirements();
+        let type_index = device
+            .find_memory_type_index(requirements.memory_type_bits, properties)
+            .ok_or(CreateBufferError::NoSuitableMemoryType(properties))?;
+
+        let memory = MemoryBuilder::new(requirements.size, type_index).build(device)?;
+
+        unsafe { buffer.bind_memory(&memory, 0)? };
+        Ok((buffer, memory))
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Buffer {
     unique_buffer: Arc<UniqueBuffer>,
 }
@@ -73,11 +122,22 @@ impl Buffer {
     /// # Safety
     /// TODO
     pub unsafe fn handle(&self) -> &vk::Buffer {
-        &self.unique_buffer.handle()
+        self.unique_buffer.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_buffer)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_buffer, &other.unique_buffer)
     }
 
     pub fn device(&self) -> &Device {
-        &self.unique_buffer.device()
+        self.unique_buffer.device()
     }
 
     pub fn size(&self) -> u64 {
@@ -87,6 +147,179 @@ impl Buffer {
     pub fn usage(&self) -> vk::BufferUsageFlags {
         self.unique_buffer.usage()
     }
+
+    pub fn memory_requirements(&self) -> vk::MemoryRequirements {
+        unsafe {
+            self.device()
+                .handle()
+                .get_buffer_memory_requirements(*self.handle())
+        }
+    }
+
+    /// # Safety
+    /// `memory` must outlive all usages of the buffer.
+    /// Buffer must not be bound to memory already.
+    pub unsafe fn bind_memory(&self, memory: &Memory, offset: u64) -> VkResult<()> {
+        let result =
+            self.device()
+                .handle()
+                .bind_buffer_memory(*self.handle(), *memory.handle(), offset);
+        self.device().track_lost(result)?;
+        self.unique_buffer.set_bound();
+        Ok(())
+    }
+
+    /// Returns `true` if memory was bound with `bind_memory`.
+    /// Binding memory through raw handle isn't tracked.
+    pub fn is_bound(&self) -> bool {
+        self.unique_buffer.is_bound()
+    }
+
+    /// Describes whole buffer for descriptor writes.
+    /// Buffer must be alive while descriptor is in use.
+    pub fn descriptor_info(&self) -> vk::DescriptorBufferInfo {
+        self.descriptor_info_range(0, vk::WHOLE_SIZE)
+    }
+
+    /// Describes `range` bytes of buffer, starting from `offset`, for descriptor writes.
+    /// Buffer must be alive while descriptor is in use.
+    pub fn descriptor_info_range(&self, offset: u64, range: u64) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo {
+            buffer: unsafe { *self.handle() },
+            offset,
+            range,
+        }
+    }
+
+    /// Returns buffer device address or `None` if buffer isn't created
+    /// with `SHADER_DEVICE_ADDRESS` usage.
+    /// Device must be created with Vulkan 1.2 API and `bufferDeviceAddress` feature enabled.
+    /// Buffer must be bound to memory, allocated with `DEVICE_ADDRESS` allocate flag
+    /// (see `MemoryBuilder::with_allocate_flags`).
+    pub fn device_address(&self) -> Option<u64> {
+        if !self
+            .usage()
+            .contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+        {
+            return None;
+        }
+
+        let info = vk::BufferDeviceAddressInfo {
+            buffer: unsafe { *self.handle() },
+            ..Default::default()
+        };
+        Some(unsafe { self.device().handle().get_buffer_device_address(&info) })
+    }
+
+    /// Copies `data` to the beginning of the buffer through temporary host visible staging buffer
+    /// and waits until copy is finished.
+    /// Buffer must be bound to memory with `bind_memory` and created with `TRANSFER_DST` usage.
+    /// `pool` must be created for `queue` family.
+    pub fn upload_blocking(
+        &self,
+        queue: &Queue,
+        pool: &CommandPool,
+        data: &[u8],
+    ) -> TransferResult<()> {
+        if !self.usage().contains(vk::BufferUsageFlags::TRANSFER_DST) {
+            return Err(TransferError::MissingUsage(
+                vk::BufferUsageFlags::TRANSFER_DST,
+            ));
+        }
+        if !self.is_bound() {
+            return Err(TransferError::NotBound);
+        }
+        if data.len() as u64 > self.size() {
+            return Err(TransferError::DataTooLarge {
+                data_size: data.len() as u64,
+                buffer_size: self.size(),
+            });
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let size = data.len() as u64;
+        let (staging, staging_memory) = staging_buffer(self.device(), size)?;
+        unsafe {
+            let ptr = staging_memory.map(0, size)?;
+            ptr::copy_nonoverlapping(data.as_ptr(), ptr.cast(), data.len());
+            staging_memory.unmap();
+        }
+
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size,
+        };
+        Ok(queue.copy_buffer_blocking(pool, &staging, self, &[region])?)
+    }
+
+    /// Copies whole buffer content to the host through temporary host visible staging buffer.
+    /// Waits until copy is finished.
+    /// Buffer must be bound to memory with `bind_memory` and created with `TRANSFER_SRC` usage.
+    /// `pool` must be created for `queue` family.
+    pub fn download_blocking(&self, queue: &Queue, pool: &CommandPool) -> TransferResult<Vec<u8>> {
+        if !self.usage().contains(vk::BufferUsageFlags::TRANSFER_SRC) {
+            return Err(TransferError::MissingUsage(
+                vk::BufferUsageFlags::TRANSFER_SRC,
+            ));
+        }
+        if !self.is_bound() {
+            return Err(TransferError::NotBound);
+        }
+
+        let size = self.size();
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+
+        let (staging, staging_memory) = staging_buffer(self.device(), size)?;
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size,
+        };
+        queue.copy_buffer_blocking(pool, self, &staging, &[region])?;
+
+        let mut data = vec![0; size as usize];
+        unsafe {
+            let ptr = staging_memory.map(0, size)?;
+            ptr::copy_nonoverlapping(ptr.cast(), data.as_mut_ptr(), data.len());
+            staging_memory.unmap();
+        }
+        Ok(data)
+    }
+}
+
+/// Returns family indices to pass to Vulkan for `sharing_mode`.
+fn sharing_indices(
+    sharing_mode: vk::SharingMode,
+    queues_family_indices: &[u32],
+) -> CreateBufferResult<&[u32]> {
+    if sharing_mode != vk::SharingMode::CONCURRENT {
+        return Ok(&[]);
+    }
+
+    let mut distinct = queues_family_indices.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+    if distinct.len() < 2 || distinct.len() != queues_family_indices.len() {
+        return Err(CreateBufferError::InvalidSharing);
+    }
+    Ok(queues_family_indices)
+}
+
+/// Creates host visible and coherent transfer buffer with bound memory.
+fn staging_buffer(device: &Device, size: u64) -> CreateBufferResult<(Buffer, Memory)> {
+    BufferBuilder::default()
+        .with_size(size)
+        .with_usage(vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST)
+        .build_with_memory(
+            device.clone(),
+            &[],
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
 }
 
 struct UniqueBuffer {
@@ -94,6 +327,7 @@ struct UniqueBuffer {
     device: Device,
     size: u64,
     usage: vk::BufferUsageFlags,
+    bound: AtomicBool,
 }
 
 impl UniqueBuffer {
@@ -102,18 +336,22 @@ impl UniqueBuffer {
         create_info: &vk::BufferCreateInfo,
     ) -> CreateBufferResult<Self> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Creating vk buffer with size: {} and usage: {:?}",
             create_info.size,
             create_info.usage
         );
 
-        let handle = device.handle().create_buffer(create_info, None)?;
+        let handle = device
+            .handle()
+            .create_buffer(create_info, device.allocation_callbacks())?;
 
         Ok(Self {
             handle,
             device,
             size: create_info.size,
             usage: create_info.usage,
+            bound: AtomicBool::new(false),
         })
     }
 
@@ -132,17 +370,38 @@ impl UniqueBuffer {
     pub fn usage(&self) -> vk::BufferUsageFlags {
         self.usage
     }
+
+    pub fn is_bound(&self) -> bool {
+        self.bound.load(Ordering::Acquire)
+    }
+
+    pub fn set_bound(&self) {
+        self.bound.store(true, Ordering::Release)
+    }
 }
 
 impl Drop for UniqueBuffer {
     fn drop(&mut self) {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Destroying buffer with size: {} and usage: {:?}",
             self.size,
             self.usage
         );
 
-        unsafe { self.device.handle().destroy_buffer(self.handle, None) }
+        unsafe {
+            self.device
+                .handle()
+                .destroy_buffer(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Buffer")
+            .field("handle", &self.handle)
+            .finish()
     }
 }
 
@@ -154,11 +413,23 @@ impl PartialEq for UniqueBuffer {
     }
 }
 
+impl Hash for UniqueBuffer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 pub type CreateBufferResult<T> = Result<T, CreateBufferError>;
 
 #[derive(Debug)]
 pub enum CreateBufferError {
     VkError(vk::Result),
+    DeviceLost,
+    InvalidSharing,
+    NoSuitableMemoryType(vk::MemoryPropertyFlags),
+    /// Memory can't be exported and imported at the same time.
+    #[cfg(feature = "external_memory")]
+    ExportWithImport,
 }
 
 impl Error for CreateBufferError {}
@@ -166,13 +437,364 @@ impl Error for CreateBufferError {}
 impl fmt::Display for CreateBufferError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::VkError(e) => write!(f, "Can't create buffer: {}", e),
+            Self::VkError(e) => write!(
+                f,
+                "Can't create buffer: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create buffer: device lost"),
+            Self::InvalidSharing => write!(
+                f,
+                "Can't create buffer: concurrent sharing requires at least two distinct, unique family indices"
+            ),
+            Self::NoSuitableMemoryType(properties) => write!(
+                f,
+                "Can't allocate buffer memory: no suitable memory type with properties {:?}",
+                properties
+            ),
+            #[cfg(feature = "external_memory")]
+            Self::ExportWithImport => write!(
+                f,
+                "Can't allocate buffer memory: memory can't be both exported and imported"
+            ),
         }
     }
 }
 
 impl From<vk::Result> for CreateBufferError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+impl From<MemAllocError> for CreateBufferError {
+    fn from(e: MemAllocError) -> Self {
+        match e {
+            MemAllocError::VkError(e) => Self::VkError(e),
+            MemAllocError::DeviceLost => Self::DeviceLost,
+            #[cfg(feature = "external_memory")]
+            MemAllocError::ExportWithImport => Self::ExportWithImport,
+        }
+    }
+}
+
+pub type TransferResult<T> = Result<T, TransferError>;
+
+#[derive(Debug)]
+pub enum TransferError {
+    VkError(vk::Result),
+    DeviceLost,
+    NoSuitableMemoryType(vk::MemoryPropertyFlags),
+    /// Staging buffer can't be created.
+    StagingBuffer(CreateBufferError),
+    NotBound,
+    /// Buffer isn't created with required usage.
+    MissingUsage(vk::BufferUsageFlags),
+    /// Command pool is created for family, that differs from family of the queue.
+    WrongQueueFamily {
+        pool_family: u32,
+        queue_family: u32,
+    },
+    DataTooLarge {
+        data_size: u64,
+        buffer_size: u64,
+    },
+}
+
+impl Error for TransferError {}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't transfer buffer data: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't transfer buffer data: device lost"),
+            Self::NoSuitableMemoryType(properties) => write!(
+                f,
+                "Can't create staging buffer: no suitable memory type with properties {:?}",
+                properties
+            ),
+            Self::StagingBuffer(e) => write!(f, "Can't create staging buffer: {}", e),
+            Self::NotBound => write!(
+                f,
+                "Can't transfer buffer data: buffer isn't bound to memory"
+            ),
+            Self::MissingUsage(usage) => write!(
+                f,
+                "Can't transfer buffer data: buffer isn't created with {:?} usage",
+                usage
+            ),
+            Self::WrongQueueFamily {
+                pool_family,
+                queue_family,
+            } => write!(
+                f,
+                "Can't transfer buffer data with pool of family {} and queue of family {}",
+                pool_family, queue_family
+            ),
+            Self::DataTooLarge {
+                data_size,
+                buffer_size,
+            } => write!(
+                f,
+                "Can't upload {} bytes to buffer with size {}",
+                data_size, buffer_size
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for TransferError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+impl From<CreateBufferError> for TransferError {
+    fn from(e: CreateBufferError) -> Self {
+        match e {
+            CreateBufferError::VkError(e) => Self::VkError(e),
+            CreateBufferError::DeviceLost => Self::DeviceLost,
+            CreateBufferError::NoSuitableMemoryType(properties) => {
+                Self::NoSuitableMemoryType(properties)
+            }
+            e => Self::StagingBuffer(e),
+        }
+    }
+}
+
+impl From<OneTimeSubmitError> for TransferError {
+    fn from(e: OneTimeSubmitError) -> Self {
+        match e {
+            OneTimeSubmitError::VkError(e) => Self::VkError(e),
+            OneTimeSubmitError::DeviceLost => Self::DeviceLost,
+            OneTimeSubmitError::WrongQueueFamily {
+                pool_family,
+                queue_family,
+            } => Self::WrongQueueFamily {
+                pool_family,
+                queue_family,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{compute_device, compute_device_with};
+
+    #[test]
+    fn pushed_struct_is_linked_to_create_info() {
+        let mut builder = BufferBuilder::default().push_next(vk::ExternalMemoryBufferCreateInfo {
+            handle_types: vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+            ..Default::default()
+        });
+        let create_info = builder.raw_create_info(&[]);
+
+        let ext = create_info.p_next as *const vk::ExternalMemoryBufferCreateInfo;
+        assert_eq!(
+            unsafe { (*ext).s_type },
+            vk::StructureType::EXTERNAL_MEMORY_BUFFER_CREATE_INFO
+        );
+        assert_eq!(
+            unsafe { (*ext).handle_types },
+            vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD
+        );
+        assert!(unsafe { (*ext).p_next.is_null() });
+        assert!(create_info.p_queue_family_indices.is_null());
+    }
+
+    #[test]
+    fn exclusive_sharing_ignores_indices() {
+        assert_eq!(
+            sharing_indices(vk::SharingMode::EXCLUSIVE, &[0, 1]).unwrap(),
+            &[] as &[u32]
+        );
+    }
+
+    #[test]
+    fn concurrent_sharing_requires_two_unique_indices() {
+        assert_eq!(
+            sharing_indices(vk::SharingMode::CONCURRENT, &[1, 0]).unwrap(),
+            &[1, 0]
+        );
+
+        for indices in &[&[][..], &[0], &[0, 0], &[0, 1, 1]] {
+            match sharing_indices(vk::SharingMode::CONCURRENT, indices) {
+                Err(CreateBufferError::InvalidSharing) => {}
+                r => panic!("indices {:?} must be rejected, got {:?}", indices, r),
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn descriptor_info_covers_whole_buffer() {
+        let (device, _queue, _pool) = compute_device();
+        let buffer = BufferBuilder::default()
+            .with_size(64)
+            .with_usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .build(device, &[])
+            .unwrap();
+
+        let info = buffer.descriptor_info();
+        assert_eq!(info.buffer, unsafe { *buffer.handle() });
+        assert_eq!((info.offset, info.range), (0, vk::WHOLE_SIZE));
+
+        let info = buffer.descriptor_info_range(16, 32);
+        assert_eq!((info.offset, info.range), (16, 32));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn upload_then_download_returns_same_data() {
+        let (device, queue, pool) = compute_device();
+        let (buffer, _memory) = BufferBuilder::default()
+            .with_size(16)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST)
+            .build_with_memory(device, &[], vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .unwrap();
+
+        let pattern: Vec<u8> = (0..16).collect();
+        buffer.upload_blocking(&queue, &pool, &pattern).unwrap();
+        assert_eq!(buffer.download_blocking(&queue, &pool).unwrap(), pattern);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn upload_checks_memory_and_size() {
+        let (device, queue, pool) = compute_device();
+        let unbound = BufferBuilder::default()
+            .with_size(4)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .build(device.clone(), &[])
+            .unwrap();
+        match unbound.upload_blocking(&queue, &pool, &[0; 4]) {
+            Err(TransferError::NotBound) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let not_destination = BufferBuilder::default()
+            .with_size(4)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_SRC)
+            .build(device.clone(), &[])
+            .unwrap();
+        match not_destination.upload_blocking(&queue, &pool, &[0; 4]) {
+            Err(TransferError::MissingUsage(usage)) => {
+                assert_eq!(usage, vk::BufferUsageFlags::TRANSFER_DST)
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let (bound, _memory) = BufferBuilder::default()
+            .with_size(4)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .build_with_memory(device, &[], vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .unwrap();
+        match bound.upload_blocking(&queue, &pool, &[0; 8]) {
+            Err(TransferError::DataTooLarge {
+                data_size: 8,
+                buffer_size: 4,
+            }) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn download_requires_transfer_src_usage() {
+        let (device, queue, pool) = compute_device();
+        let (buffer, _memory) = BufferBuilder::default()
+            .with_size(4)
+            .with_usage(vk::BufferUsageFlags::TRANSFER_DST)
+            .build_with_memory(device, &[], vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .unwrap();
+        match buffer.download_blocking(&queue, &pool) {
+            Err(TransferError::MissingUsage(usage)) => {
+                assert_eq!(usage, vk::BufferUsageFlags::TRANSFER_SRC)
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device with bufferDeviceAddress feature"]
+    fn device_address_requires_usage() {
+        let (device, _queue, _pool) = compute_device_with(|builder| {
+            builder.with_feature_struct(vk::PhysicalDeviceVulkan12Features {
+                buffer_device_address: vk::TRUE,
+                ..Default::default()
+            })
+        });
+        let buffer = BufferBuilder::default()
+            .with_size(64)
+            .with_usage(
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            )
+            .build(device.clone(), &[])
+            .unwrap();
+        let requirements = buffer.memory_requirements();
+        let type_index = device
+            .find_memory_type_index(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::empty(),
+            )
+            .unwrap();
+        let memory = MemoryBuilder::new(requirements.size, type_index)
+            .with_allocate_flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS)
+            .build(device.clone())
+            .unwrap();
+        unsafe { buffer.bind_memory(&memory, 0).unwrap() };
+        assert!(buffer.device_address().unwrap() != 0);
+
+        let without_usage = BufferBuilder::default()
+            .with_size(64)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build(device, &[])
+            .unwrap();
+        assert_eq!(without_usage.device_address(), None);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn buffer_is_built_with_bound_memory() {
+        let (device, _queue, _pool) = compute_device();
+        let properties =
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let (buffer, memory) = BufferBuilder::default()
+            .with_size(256)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build_with_memory(device.clone(), &[], properties)
+            .unwrap();
+
+        assert!(buffer.is_bound());
+        assert!(memory.device().same_instance(&device));
+        unsafe {
+            let ptr = memory.map(0, 256).unwrap();
+            ptr.cast::<u8>().write_bytes(0xAB, 256);
+            memory.unmap();
+        }
+
+        let bogus = vk::MemoryPropertyFlags::from_raw(1 << 31);
+        match BufferBuilder::default()
+            .with_size(256)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build_with_memory(device, &[], bogus)
+        {
+            Err(CreateBufferError::NoSuitableMemoryType(p)) => assert_eq!(p, bogus),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
     }
 }
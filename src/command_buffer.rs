@@ -1,10 +1,12 @@
 use crate::command_pool::CommandPool;
 use crate::device::Device;
+use crate::queue::Queue;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use ash::vk::CommandBufferLevel;
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 pub struct CommandBuffersBuilder {
@@ -13,6 +15,7 @@ pub struct CommandBuffersBuilder {
 }
 
 impl CommandBuffersBuilder {
+    /// `SECONDARY` command buffers require inheritance info when recording begins.
     pub fn with_level(mut self, level: vk::CommandBufferLevel) -> Self {
         self.level = level;
         self
@@ -48,7 +51,7 @@ impl Default for CommandBuffersBuilder {
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct CommandBuffers {
     command_buffers: Arc<UniqueCommandBuffers>,
 }
@@ -67,15 +70,19 @@ impl CommandBuffers {
     }
 
     pub fn pool(&self) -> &CommandPool {
-        &self.command_buffers.pool()
+        self.command_buffers.pool()
     }
 
     pub fn device(&self) -> &Device {
-        &self.command_buffers.device()
+        self.command_buffers.device()
     }
 
     pub fn level(&self) -> &vk::CommandBufferLevel {
-        &self.command_buffers.level()
+        self.command_buffers.level()
+    }
+
+    pub fn is_secondary(&self) -> bool {
+        *self.level() == vk::CommandBufferLevel::SECONDARY
     }
 
     /// # Safety
@@ -84,15 +91,91 @@ impl CommandBuffers {
         self.command_buffers.handle(index)
     }
 
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.command_buffers)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.command_buffers, &other.command_buffers)
+    }
+
     pub fn len(&self) -> usize {
         self.command_buffers.len()
     }
 
+    /// Allocates `count` more command buffers from the same pool with the same level.
+    pub fn allocate_more(&self, count: u32) -> AllocateCommandBuffersResult<CommandBuffers> {
+        CommandBuffersBuilder::default()
+            .with_level(*self.level())
+            .with_count(count)
+            .build(self.pool().clone(), self.device().clone())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.command_buffers.is_empty()
     }
 }
 
+/// Primary command buffer for single submission of ad-hoc work.
+/// Command buffer is freed on drop, even if submission failed.
+pub struct OneTimeSubmit {
+    command_buffers: CommandBuffers,
+}
+
+impl OneTimeSubmit {
+    /// Allocates command buffer from `pool` and begins recording.
+    pub fn begin(pool: &CommandPool, device: &Device) -> OneTimeSubmitResult<Self> {
+        let command_buffers =
+            CommandBuffersBuilder::default().build(pool.clone(), device.clone())?;
+
+        let begin_info = vk::CommandBufferBeginInfo {
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            ..Default::default()
+        };
+
+        let one_time_submit = Self { command_buffers };
+        let result = unsafe {
+            device
+                .handle()
+                .begin_command_buffer(one_time_submit.recorder(), &begin_info)
+        };
+        device.track_lost(result)?;
+        Ok(one_time_submit)
+    }
+
+    /// Returns command buffer in recording state.
+    ///
+    /// # Safety
+    /// Recorded commands must be valid and resources, used by them,
+    /// must outlive `submit_and_wait` call.
+    pub unsafe fn recorder(&self) -> vk::CommandBuffer {
+        *self.command_buffers.handle(0).unwrap()
+    }
+
+    /// Ends recording, submits command buffer to `queue` and waits until it's executed.
+    /// `queue` must be of family, that command pool was created for.
+    pub fn submit_and_wait(self, queue: &Queue) -> OneTimeSubmitResult<()> {
+        let pool_family = self.command_buffers.pool().queue_family_index();
+        if pool_family != queue.family_index() {
+            return Err(OneTimeSubmitError::WrongQueueFamily {
+                pool_family,
+                queue_family: queue.family_index(),
+            });
+        }
+
+        let device = self.command_buffers.device();
+        unsafe {
+            let command_buffer = self.recorder();
+            let result = device.handle().end_command_buffer(command_buffer);
+            device.track_lost(result)?;
+            Ok(queue.submit_and_wait(&[command_buffer])?)
+        }
+    }
+}
+
 struct UniqueCommandBuffers {
     handles: Vec<vk::CommandBuffer>,
     pool: CommandPool,
@@ -107,6 +190,7 @@ impl UniqueCommandBuffers {
         pool: CommandPool,
     ) -> AllocateCommandBuffersResult<Self> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Allocating {} command buffers with level: {:?}",
             allocate_info.command_buffer_count,
             allocate_info.level
@@ -152,6 +236,7 @@ impl UniqueCommandBuffers {
 impl Drop for UniqueCommandBuffers {
     fn drop(&mut self) {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Destroying {} command buffers with level: {:?}",
             self.handles.len(),
             self.level
@@ -164,6 +249,14 @@ impl Drop for UniqueCommandBuffers {
     }
 }
 
+impl fmt::Debug for UniqueCommandBuffers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommandBuffers")
+            .field("handles", &self.handles)
+            .finish()
+    }
+}
+
 impl Eq for UniqueCommandBuffers {}
 
 impl PartialEq for UniqueCommandBuffers {
@@ -172,11 +265,18 @@ impl PartialEq for UniqueCommandBuffers {
     }
 }
 
+impl Hash for UniqueCommandBuffers {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handles.hash(state)
+    }
+}
+
 pub type AllocateCommandBuffersResult<T> = Result<T, AllocateCommandBuffersError>;
 
 #[derive(Debug)]
 pub enum AllocateCommandBuffersError {
     VkError(vk::Result),
+    DeviceLost,
 }
 
 impl Error for AllocateCommandBuffersError {}
@@ -184,13 +284,128 @@ impl Error for AllocateCommandBuffersError {}
 impl fmt::Display for AllocateCommandBuffersError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::VkError(e) => write!(f, "Can't allocate command buffers: {}", e),
+            Self::VkError(e) => write!(
+                f,
+                "Can't allocate command buffers: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't allocate command buffers: device lost"),
         }
     }
 }
 
 impl From<vk::Result> for AllocateCommandBuffersError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+pub type OneTimeSubmitResult<T> = Result<T, OneTimeSubmitError>;
+
+#[derive(Debug)]
+pub enum OneTimeSubmitError {
+    VkError(vk::Result),
+    DeviceLost,
+    /// Command pool is created for family, that differs from family of the queue.
+    WrongQueueFamily {
+        pool_family: u32,
+        queue_family: u32,
+    },
+}
+
+impl Error for OneTimeSubmitError {}
+
+impl fmt::Display for OneTimeSubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't submit one time command buffer: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't submit one time command buffer: device lost"),
+            Self::WrongQueueFamily {
+                pool_family,
+                queue_family,
+            } => write!(
+                f,
+                "Can't submit command buffer from pool of family {} to queue of family {}",
+                pool_family, queue_family
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for OneTimeSubmitError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+impl From<AllocateCommandBuffersError> for OneTimeSubmitError {
+    fn from(e: AllocateCommandBuffersError) -> Self {
+        match e {
+            AllocateCommandBuffersError::VkError(e) => Self::VkError(e),
+            AllocateCommandBuffersError::DeviceLost => Self::DeviceLost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device;
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn noop_is_submitted_once() {
+        let (device, queue, pool) = compute_device();
+        let one_time_submit = OneTimeSubmit::begin(&pool, &device).unwrap();
+        one_time_submit.submit_and_wait(&queue).unwrap();
+        assert_eq!(pool.strong_count(), 1);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn more_buffers_are_allocated_from_same_pool() {
+        let (device, _queue, pool) = compute_device();
+        let buffers = CommandBuffersBuilder::default()
+            .with_level(vk::CommandBufferLevel::SECONDARY)
+            .with_count(4)
+            .build(pool.clone(), device)
+            .unwrap();
+        let more = buffers.allocate_more(2).unwrap();
+
+        assert_eq!(buffers.len(), 4);
+        assert_eq!(more.len(), 2);
+        assert!(more.pool().same_instance(&pool));
+        assert_eq!(more.level(), buffers.level());
+        assert!(unsafe { more.handle(1) }.is_some());
+        assert!(unsafe { more.handle(2) }.is_none());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn level_is_reported() {
+        let (device, _queue, pool) = compute_device();
+        let primary = CommandBuffersBuilder::default()
+            .build(pool.clone(), device.clone())
+            .unwrap();
+        let secondary = CommandBuffersBuilder::default()
+            .with_level(vk::CommandBufferLevel::SECONDARY)
+            .build(pool, device)
+            .unwrap();
+
+        assert_eq!(*primary.level(), vk::CommandBufferLevel::PRIMARY);
+        assert!(!primary.is_secondary());
+        assert!(secondary.is_secondary());
     }
 }
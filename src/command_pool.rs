@@ -1,9 +1,12 @@
 use crate::device::Device;
 use ash::version::DeviceV1_0;
 use ash::vk;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
 
 pub struct CommandPoolBuilder {
     flags: vk::CommandPoolCreateFlags,
@@ -34,7 +37,7 @@ impl CommandPoolBuilder {
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct CommandPool {
     unique_command_pool: Arc<UniqueCommandPool>,
 }
@@ -54,11 +57,22 @@ impl CommandPool {
     /// # Safety
     /// TODO
     pub unsafe fn handle(&self) -> &vk::CommandPool {
-        &self.unique_command_pool.handle()
+        self.unique_command_pool.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_command_pool)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_command_pool, &other.unique_command_pool)
     }
 
     pub fn device(&self) -> &Device {
-        &self.unique_command_pool.device()
+        self.unique_command_pool.device()
     }
 
     pub fn queue_family_index(&self) -> u32 {
@@ -70,6 +84,63 @@ impl CommandPool {
     }
 }
 
+/// Lazily creates one command pool per thread, so command buffers can be recorded
+/// from several threads without external synchronization of pools.
+///
+/// Pools are kept until helper is dropped, even if their threads finished.
+/// Short-lived threads should call `remove_current_thread` before exit.
+pub struct ThreadLocalPools {
+    device: Device,
+    queue_family_index: u32,
+    flags: vk::CommandPoolCreateFlags,
+    pools: Mutex<HashMap<ThreadId, CommandPool>>,
+}
+
+impl ThreadLocalPools {
+    pub fn new(device: Device, queue_family_index: u32, flags: vk::CommandPoolCreateFlags) -> Self {
+        Self {
+            device,
+            queue_family_index,
+            flags,
+            pools: Default::default(),
+        }
+    }
+
+    /// Calls `f` with pool of current thread. Pool is created on first call from the thread.
+    /// Command buffers, allocated from the pool, must be recorded, reset and freed on the same thread.
+    pub fn with_pool<R>(&self, f: impl FnOnce(&CommandPool) -> R) -> CreateCommandPoolResult<R> {
+        let pool = {
+            let mut pools = self.pools.lock().unwrap();
+            match pools.get(&thread::current().id()) {
+                Some(pool) => pool.clone(),
+                None => {
+                    let pool = CommandPoolBuilder::new(self.queue_family_index)
+                        .with_flags(self.flags)
+                        .build(self.device.clone())?;
+                    pools.insert(thread::current().id(), pool.clone());
+                    pool
+                }
+            }
+        };
+
+        Ok(f(&pool))
+    }
+
+    /// Forgets pool of current thread and returns it, if it was created.
+    /// Pool is destroyed when returned value and all command buffers, allocated from it, are dropped.
+    pub fn remove_current_thread(&self) -> Option<CommandPool> {
+        self.pools.lock().unwrap().remove(&thread::current().id())
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn queue_family_index(&self) -> u32 {
+        self.queue_family_index
+    }
+}
+
 #[derive(Eq, PartialEq)]
 struct UniqueCommandPool {
     handle: vk::CommandPool,
@@ -78,17 +149,34 @@ struct UniqueCommandPool {
     flags: vk::CommandPoolCreateFlags,
 }
 
+impl fmt::Debug for UniqueCommandPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CommandPool")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Hash for UniqueCommandPool {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 impl UniqueCommandPool {
     pub unsafe fn new(
         device: Device,
         create_info: &vk::CommandPoolCreateInfo,
     ) -> CreateCommandPoolResult<Self> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Creating command pool for queue family: {} and flags: {:?}",
             create_info.queue_family_index,
             create_info.flags
         );
-        let handle = device.handle().create_command_pool(create_info, None)?;
+        let handle = device
+            .handle()
+            .create_command_pool(create_info, device.allocation_callbacks())?;
         Ok(Self {
             handle,
             device,
@@ -117,11 +205,16 @@ impl UniqueCommandPool {
 impl Drop for UniqueCommandPool {
     fn drop(&mut self) {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Creating command pool for queue family: {} and flags: {:?}",
             self.queue_family_index,
             self.flags
         );
-        unsafe { self.device.handle().destroy_command_pool(self.handle, None) }
+        unsafe {
+            self.device
+                .handle()
+                .destroy_command_pool(self.handle, self.device.allocation_callbacks())
+        }
     }
 }
 
@@ -130,6 +223,7 @@ pub type CreateCommandPoolResult<T> = Result<T, CreateCommandPoolError>;
 #[derive(Debug)]
 pub enum CreateCommandPoolError {
     VkError(vk::Result),
+    DeviceLost,
 }
 
 impl Error for CreateCommandPoolError {}
@@ -137,13 +231,62 @@ impl Error for CreateCommandPoolError {}
 impl fmt::Display for CreateCommandPoolError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::VkError(e) => write!(f, "Can't create command pool: {}", e),
+            Self::VkError(e) => write!(
+                f,
+                "Can't create command pool: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create command pool: device lost"),
         }
     }
 }
 
 impl From<vk::Result> for CreateCommandPoolError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device;
+    use std::sync::Arc;
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn each_thread_gets_own_pool() {
+        let (device, queue, _pool) = compute_device();
+        let pools = Arc::new(ThreadLocalPools::new(
+            device,
+            queue.family_index(),
+            vk::CommandPoolCreateFlags::empty(),
+        ));
+
+        let main_pool = pools.with_pool(|pool| pool.clone()).unwrap();
+        assert!(pools
+            .with_pool(|pool| pool.same_instance(&main_pool))
+            .unwrap());
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pools = pools.clone();
+                thread::spawn(move || pools.with_pool(|pool| pool.clone()).unwrap())
+            })
+            .collect();
+        let mut thread_pools: Vec<CommandPool> =
+            threads.into_iter().map(|t| t.join().unwrap()).collect();
+        thread_pools.push(main_pool);
+
+        for (i, pool) in thread_pools.iter().enumerate() {
+            assert!(thread_pools[i + 1..].iter().all(|other| other != pool));
+        }
+
+        assert!(pools.remove_current_thread().is_some());
+        assert!(pools.remove_current_thread().is_none());
     }
 }
@@ -4,6 +4,7 @@ use ash::vk;
 use std::error::Error;
 use std::ffi::CStr;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::os::raw::{c_char, c_void};
 use std::sync::Arc;
 
@@ -66,7 +67,43 @@ impl From<MessageLevel> for log::Level {
     }
 }
 
-pub struct Callback(pub Box<dyn Fn(String, MessageLevel) + 'static>);
+#[derive(Debug, Clone)]
+pub struct DebugMessage {
+    pub message: String,
+    pub level: MessageLevel,
+    pub object_type: vk::DebugReportObjectTypeEXT,
+    pub object_handle: u64,
+    pub location: usize,
+    pub message_code: i32,
+    pub layer_prefix: String,
+}
+
+impl fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} (code: {}; object: {:?} {:#x}; location: {}): {}",
+            self.level,
+            self.layer_prefix,
+            self.message_code,
+            self.object_type,
+            self.object_handle,
+            self.location,
+            self.message
+        )
+    }
+}
+
+/// Vulkan implementation may report messages from any thread, so callback must be `Send + Sync`.
+pub struct Callback(pub Box<dyn Fn(DebugMessage) + Send + Sync + 'static>);
+
+/// Pointer to leaked callback, owned by `UniqueDebugReport`.
+struct CallbackPtr(*mut Callback);
+
+// SAFETY: pointer is only dereferenced as shared reference to `Callback`, which is `Send + Sync`.
+// It's freed once, when `UniqueDebugReport` is dropped.
+unsafe impl Send for CallbackPtr {}
+unsafe impl Sync for CallbackPtr {}
 
 pub struct DebugReportBuilder {
     callback: Callback,
@@ -75,7 +112,7 @@ pub struct DebugReportBuilder {
 
 impl Default for DebugReportBuilder {
     fn default() -> Self {
-        let callback = |msg, level| println!("Vulkan callback report [{}]: {}", level, msg);
+        let callback = |msg| println!("Vulkan callback report {}", msg);
         Self {
             callback: Callback(Box::new(callback)),
             flags: vk::DebugReportFlagsEXT::all(),
@@ -89,6 +126,29 @@ impl DebugReportBuilder {
         self
     }
 
+    /// Sets flags, that match `level` filter:
+    /// - `Off`: no flags;
+    /// - `Error`: `ERROR`;
+    /// - `Warn`: `ERROR`, `WARNING` and `PERFORMANCE_WARNING`;
+    /// - `Info`: `Warn` flags and `INFORMATION`;
+    /// - `Debug` and `Trace`: `Info` flags and `DEBUG`.
+    pub fn with_flags_from_log_level(self, level: log::LevelFilter) -> Self {
+        let error = vk::DebugReportFlagsEXT::ERROR;
+        let warn =
+            error | vk::DebugReportFlagsEXT::WARNING | vk::DebugReportFlagsEXT::PERFORMANCE_WARNING;
+        let info = warn | vk::DebugReportFlagsEXT::INFORMATION;
+        let debug = info | vk::DebugReportFlagsEXT::DEBUG;
+
+        let flags = match level {
+            log::LevelFilter::Off => vk::DebugReportFlagsEXT::empty(),
+            log::LevelFilter::Error => error,
+            log::LevelFilter::Warn => warn,
+            log::LevelFilter::Info => info,
+            log::LevelFilter::Debug | log::LevelFilter::Trace => debug,
+        };
+        self.with_flags(flags)
+    }
+
     pub fn with_callback(mut self, callback: Callback) -> Self {
         self.callback = callback;
         self
@@ -110,8 +170,8 @@ impl DebugReportBuilder {
     }
 
     pub fn default_logger_callback() -> Callback {
-        let callback = |msg, level: MessageLevel| {
-            log::log!(level.into(), "Vulkan report: {}", msg);
+        let callback = |msg: DebugMessage| {
+            log::log!(target: crate::LOG_TARGET, msg.level.into(), "Vulkan report: {}", msg);
         };
         Callback(Box::new(callback))
     }
@@ -119,28 +179,41 @@ impl DebugReportBuilder {
 
 unsafe extern "system" fn debug_report_callback(
     flags: vk::DebugReportFlagsEXT,
-    _: vk::DebugReportObjectTypeEXT,
-    _: u64,
-    _: usize,
-    _: i32,
-    _: *const c_char,
+    object_type: vk::DebugReportObjectTypeEXT,
+    object_handle: u64,
+    location: usize,
+    message_code: i32,
+    p_layer_prefix: *const c_char,
     p_message: *const c_char,
     p_user_data: *mut c_void,
 ) -> vk::Bool32 {
     let callback: *mut Callback = p_user_data.cast();
     let callback_ref = callback.as_ref();
-    let msg = CStr::from_ptr(p_message);
-    let str = msg.to_string_lossy();
-    let level = flags.into();
+    let message = DebugMessage {
+        message: c_str_to_string(p_message),
+        level: flags.into(),
+        object_type,
+        object_handle,
+        location,
+        message_code,
+        layer_prefix: c_str_to_string(p_layer_prefix),
+    };
     match callback_ref {
-        Some(cb) => cb.0(format!("{}", str), level),
+        Some(cb) => cb.0(message),
         None => eprintln!("Can't dereference vk debug report callback pointer"),
     }
 
     vk::FALSE
 }
 
-#[derive(Clone, Eq, PartialEq)]
+unsafe fn c_str_to_string(ptr: *const c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
 pub struct DebugReport {
     unique_debug_report: Arc<UniqueDebugReport>,
 }
@@ -161,11 +234,32 @@ impl DebugReport {
     ///# Safety
     /// TODO
     pub unsafe fn handle(&self) -> &vk::DebugReportCallbackEXT {
-        &self.unique_debug_report.handle()
+        self.unique_debug_report.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_debug_report)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_debug_report, &other.unique_debug_report)
     }
 
     pub fn instance(&self) -> &Instance {
-        &self.unique_debug_report.instance()
+        self.unique_debug_report.instance()
+    }
+
+    /// Destroys debug report and its callback right now.
+    /// If other clones of this handle are alive, nothing is destroyed and `self` is returned back.
+    pub fn destroy(self) -> Result<(), Self> {
+        Arc::try_unwrap(self.unique_debug_report)
+            .map(drop)
+            .map_err(|unique_debug_report| Self {
+                unique_debug_report,
+            })
     }
 }
 
@@ -173,7 +267,7 @@ struct UniqueDebugReport {
     instance: Instance,
     debug_report: ext::DebugReport,
     handle: vk::DebugReportCallbackEXT,
-    callback: *mut Callback,
+    callback: CallbackPtr,
 }
 
 impl UniqueDebugReport {
@@ -183,17 +277,18 @@ impl UniqueDebugReport {
         callback: *mut Callback,
     ) -> DebugReportResult<Self> {
         let level: MessageLevel = create_info.flags.into();
-        log::trace!("Creating vk debug report with level: {}", level);
+        log::trace!(target: crate::LOG_TARGET, "Creating vk debug report with level: {}", level);
 
         let instance_raw = instance.handle().clone();
         let debug_report = ext::DebugReport::new(instance.entry(), &instance_raw);
-        let handle = debug_report.create_debug_report_callback(create_info, None)?;
+        let handle = debug_report
+            .create_debug_report_callback(create_info, instance.allocation_callbacks())?;
 
         Ok(Self {
             debug_report,
             handle,
             instance,
-            callback,
+            callback: CallbackPtr(callback),
         })
     }
 
@@ -208,11 +303,11 @@ impl UniqueDebugReport {
 
 impl Drop for UniqueDebugReport {
     fn drop(&mut self) {
-        log::trace!("Destroying vk debug report with it's callback");
+        log::trace!(target: crate::LOG_TARGET, "Destroying vk debug report with it's callback");
         unsafe {
             self.debug_report
-                .destroy_debug_report_callback(self.handle, None);
-            let _cb = Box::from_raw(self.callback);
+                .destroy_debug_report_callback(self.handle, self.instance.allocation_callbacks());
+            let _cb = Box::from_raw(self.callback.0);
         }
     }
 }
@@ -225,6 +320,12 @@ impl PartialEq for UniqueDebugReport {
     }
 }
 
+impl Hash for UniqueDebugReport {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 pub type DebugReportResult<T> = Result<T, CreateDebugReportError>;
 
 #[derive(Debug)]
@@ -238,7 +339,12 @@ impl fmt::Display for CreateDebugReportError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             CreateDebugReportError::VkError(e) => {
-                write!(f, "Vulkan debug report creation failed: {}", e)
+                write!(
+                    f,
+                    "Vulkan debug report creation failed: {} ({})",
+                    e,
+                    crate::describe_result(*e)
+                )
             }
         }
     }
@@ -249,3 +355,71 @@ impl From<vk::Result> for CreateDebugReportError {
         Self::VkError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::InstanceBuilder;
+
+    fn debug_report_instance() -> Instance {
+        let entry = ash::Entry::new().expect("Vulkan loader must be available");
+        InstanceBuilder::new(entry)
+            .with_extensions(vec![ext::DebugReport::name().to_owned()])
+            .build()
+            .expect("Instance must be created")
+    }
+
+    fn flags_for(level: log::LevelFilter) -> vk::DebugReportFlagsEXT {
+        DebugReportBuilder::default()
+            .with_flags_from_log_level(level)
+            .flags
+    }
+
+    #[test]
+    fn log_level_maps_to_report_flags() {
+        type Flags = vk::DebugReportFlagsEXT;
+        let warn = Flags::ERROR | Flags::WARNING | Flags::PERFORMANCE_WARNING;
+        let info = warn | Flags::INFORMATION;
+        let debug = info | Flags::DEBUG;
+
+        assert_eq!(flags_for(log::LevelFilter::Off), Flags::empty());
+        assert_eq!(flags_for(log::LevelFilter::Error), Flags::ERROR);
+        assert_eq!(flags_for(log::LevelFilter::Warn), warn);
+        assert_eq!(flags_for(log::LevelFilter::Info), info);
+        assert_eq!(flags_for(log::LevelFilter::Debug), debug);
+        assert_eq!(flags_for(log::LevelFilter::Trace), debug);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan loader with VK_EXT_debug_report"]
+    fn destroy_frees_callback() {
+        let token = Arc::new(());
+        let callback_token = token.clone();
+        let report = DebugReportBuilder::default()
+            .with_callback(Callback(Box::new(move |_| {
+                let _ = &callback_token;
+            })))
+            .build(debug_report_instance())
+            .unwrap();
+        assert_eq!(Arc::strong_count(&token), 2);
+
+        assert!(report.destroy().is_ok());
+        assert_eq!(Arc::strong_count(&token), 1);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan loader with VK_EXT_debug_report"]
+    fn destroy_returns_report_if_clone_is_alive() {
+        let report = DebugReportBuilder::default()
+            .build(debug_report_instance())
+            .unwrap();
+        let clone = report.clone();
+
+        let report = report.destroy().unwrap_err();
+        assert!(report.same_instance(&clone));
+        assert_eq!(report.strong_count(), 2);
+
+        drop(clone);
+        assert!(report.destroy().is_ok());
+    }
+}
@@ -43,11 +43,10 @@ impl BindingDescriptorType {
     }
 
     pub fn has_samplers(&self) -> bool {
-        match &self {
-            BindingDescriptorType::Sampler(_) => true,
-            BindingDescriptorType::CombinedImageSampler(_) => true,
-            _ => false,
-        }
+        matches!(
+            &self,
+            BindingDescriptorType::Sampler(_) | BindingDescriptorType::CombinedImageSampler(_)
+        )
     }
 }
 
@@ -97,6 +96,10 @@ impl BindingInfo {
         }
     }
 
+    pub fn index(&self) -> u32 {
+        self.raw_binding.binding
+    }
+
     pub fn samplers(&self) -> &Vec<Sampler> {
         &self.samplers
     }
@@ -113,3 +116,36 @@ impl BindingInfo {
         self.raw_binding
     }
 }
+
+/// Binding of created descriptor set layout, as it was passed to Vulkan.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BindingDescription {
+    pub index: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub descriptor_count: u32,
+    pub stage_flags: vk::ShaderStageFlags,
+    pub immutable_samplers: Vec<vk::Sampler>,
+}
+
+impl BindingDescription {
+    /// # Safety
+    /// `raw.p_immutable_samplers` must be null or point to `raw.descriptor_count` samplers.
+    pub unsafe fn from_raw(raw: &vk::DescriptorSetLayoutBinding) -> Self {
+        let has_samplers = raw.descriptor_type == vk::DescriptorType::SAMPLER
+            || raw.descriptor_type == vk::DescriptorType::COMBINED_IMAGE_SAMPLER;
+        let immutable_samplers = if has_samplers && !raw.p_immutable_samplers.is_null() {
+            std::slice::from_raw_parts(raw.p_immutable_samplers, raw.descriptor_count as usize)
+                .to_vec()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            index: raw.binding,
+            descriptor_type: raw.descriptor_type,
+            descriptor_count: raw.descriptor_count,
+            stage_flags: raw.stage_flags,
+            immutable_samplers,
+        }
+    }
+}
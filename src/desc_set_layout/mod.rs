@@ -2,11 +2,13 @@ pub mod binding;
 
 use crate::device::Device;
 use crate::sampler::Sampler;
+use ash::extensions::khr::PushDescriptor;
 use ash::version::DeviceV1_0;
 use ash::vk;
-use binding::BindingInfo;
+use binding::{BindingDescription, BindingInfo};
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 pub struct DescriptorSetLayoutBuilder {
@@ -22,13 +24,47 @@ impl DescriptorSetLayoutBuilder {
         }
     }
 
+    pub fn add_binding(mut self, binding: BindingInfo) -> Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Sets `PUSH_DESCRIPTOR_KHR` flag, so descriptors can be pushed directly to command buffer.
+    /// Device must be created with `VK_KHR_push_descriptor` extension enabled.
+    pub fn with_push_descriptor(mut self) -> Self {
+        self.flags |= vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR;
+        self
+    }
+
     pub fn build(self, device: Device) -> CreateDescriptorSetLayoutResult<DescriptorSetLayout> {
+        if self
+            .flags
+            .contains(vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR)
+            && !device.is_extension_enabled(PushDescriptor::name())
+        {
+            return Err(CreateDescriptorSetLayoutError::PushDescriptorNotEnabled);
+        }
+
         let binding_ptrs: Vec<vk::DescriptorSetLayoutBinding> = self
             .bindings
             .iter()
             .map(|b| unsafe { b.raw_binding() })
             .collect();
 
+        if let Some(duplicate) = duplicate_binding(&binding_ptrs) {
+            return Err(CreateDescriptorSetLayoutError::DuplicateBinding(duplicate));
+        }
+
+        // Update-after-bind layouts are limited by separate Vulkan 1.2 limits.
+        if !self
+            .flags
+            .contains(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+        {
+            if let Some(exceeded) = exceeded_limit(&binding_ptrs, &device.limits()) {
+                return Err(CreateDescriptorSetLayoutError::TooManyDescriptors(exceeded));
+            }
+        }
+
         let create_info = vk::DescriptorSetLayoutCreateInfo {
             binding_count: binding_ptrs.len() as u32,
             p_bindings: binding_ptrs.as_ptr(),
@@ -45,7 +81,101 @@ impl DescriptorSetLayoutBuilder {
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+impl Default for DescriptorSetLayoutBuilder {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+/// Returns first binding index, used more than once.
+fn duplicate_binding(bindings: &[vk::DescriptorSetLayoutBinding]) -> Option<u32> {
+    let mut indices: Vec<u32> = bindings.iter().map(|b| b.binding).collect();
+    indices.sort_unstable();
+    indices.windows(2).find(|w| w[0] == w[1]).map(|w| w[0])
+}
+
+/// Descriptor count of set layout, exceeding device limit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ExceededLimit {
+    /// Name of `vk::PhysicalDeviceLimits` field.
+    pub limit_name: &'static str,
+    pub count: u32,
+    pub limit: u32,
+}
+
+/// Checks total descriptor counts of `bindings` against `maxDescriptorSet*` limits.
+fn exceeded_limit(
+    bindings: &[vk::DescriptorSetLayoutBinding],
+    limits: &vk::PhysicalDeviceLimits,
+) -> Option<ExceededLimit> {
+    use vk::DescriptorType as Ty;
+
+    let count_of = |types: &[Ty]| -> u32 {
+        bindings
+            .iter()
+            .filter(|b| types.contains(&b.descriptor_type))
+            .map(|b| b.descriptor_count)
+            .sum()
+    };
+
+    let checks = [
+        (
+            "max_descriptor_set_samplers",
+            count_of(&[Ty::SAMPLER, Ty::COMBINED_IMAGE_SAMPLER]),
+            limits.max_descriptor_set_samplers,
+        ),
+        (
+            "max_descriptor_set_sampled_images",
+            count_of(&[
+                Ty::SAMPLED_IMAGE,
+                Ty::COMBINED_IMAGE_SAMPLER,
+                Ty::UNIFORM_TEXEL_BUFFER,
+            ]),
+            limits.max_descriptor_set_sampled_images,
+        ),
+        (
+            "max_descriptor_set_storage_images",
+            count_of(&[Ty::STORAGE_IMAGE, Ty::STORAGE_TEXEL_BUFFER]),
+            limits.max_descriptor_set_storage_images,
+        ),
+        (
+            "max_descriptor_set_uniform_buffers",
+            count_of(&[Ty::UNIFORM_BUFFER, Ty::UNIFORM_BUFFER_DYNAMIC]),
+            limits.max_descriptor_set_uniform_buffers,
+        ),
+        (
+            "max_descriptor_set_uniform_buffers_dynamic",
+            count_of(&[Ty::UNIFORM_BUFFER_DYNAMIC]),
+            limits.max_descriptor_set_uniform_buffers_dynamic,
+        ),
+        (
+            "max_descriptor_set_storage_buffers",
+            count_of(&[Ty::STORAGE_BUFFER, Ty::STORAGE_BUFFER_DYNAMIC]),
+            limits.max_descriptor_set_storage_buffers,
+        ),
+        (
+            "max_descriptor_set_storage_buffers_dynamic",
+            count_of(&[Ty::STORAGE_BUFFER_DYNAMIC]),
+            limits.max_descriptor_set_storage_buffers_dynamic,
+        ),
+        (
+            "max_descriptor_set_input_attachments",
+            count_of(&[Ty::INPUT_ATTACHMENT]),
+            limits.max_descriptor_set_input_attachments,
+        ),
+    ];
+
+    checks
+        .iter()
+        .find(|(_, count, limit)| count > limit)
+        .map(|&(limit_name, count, limit)| ExceededLimit {
+            limit_name,
+            count,
+            limit,
+        })
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct DescriptorSetLayout {
     descriptor_set_layout: Arc<UniqueDescriptorSetLayout>,
 }
@@ -66,20 +196,76 @@ impl DescriptorSetLayout {
     /// # Safety
     /// todo
     pub unsafe fn handle(&self) -> &vk::DescriptorSetLayout {
-        &self.descriptor_set_layout.handle()
+        self.descriptor_set_layout.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.descriptor_set_layout)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.descriptor_set_layout, &other.descriptor_set_layout)
     }
 
     pub fn device(&self) -> &Device {
-        &self.descriptor_set_layout.device()
+        self.descriptor_set_layout.device()
     }
 
     pub fn samplers(&self) -> &Vec<Sampler> {
-        &self.descriptor_set_layout.samplers()
+        self.descriptor_set_layout.samplers()
     }
 
     pub fn binding_count(&self) -> u32 {
         self.descriptor_set_layout.binding_count()
     }
+
+    /// Bindings of this layout, sorted by index.
+    pub fn bindings(&self) -> &[BindingDescription] {
+        self.descriptor_set_layout.bindings()
+    }
+
+    pub fn flags(&self) -> vk::DescriptorSetLayoutCreateFlags {
+        self.descriptor_set_layout.flags()
+    }
+
+    /// Returns `true` if layouts are identically defined, so descriptor sets, allocated with
+    /// one of them, can be used with pipelines, created with another.
+    pub fn is_compatible_with(&self, other: &DescriptorSetLayout) -> bool {
+        identically_defined(
+            self.flags(),
+            self.bindings(),
+            other.flags(),
+            other.bindings(),
+        )
+    }
+}
+
+/// Returns `true` if layouts with such flags and bindings, sorted by index, are identical.
+fn identically_defined(
+    flags: vk::DescriptorSetLayoutCreateFlags,
+    bindings: &[BindingDescription],
+    other_flags: vk::DescriptorSetLayoutCreateFlags,
+    other_bindings: &[BindingDescription],
+) -> bool {
+    flags == other_flags && bindings == other_bindings
+}
+
+/// Describes `bindings`, sorted by index.
+///
+/// # Safety
+/// Immutable samplers of `bindings` must be null or point to `descriptor_count` samplers.
+unsafe fn binding_descriptions(
+    bindings: &[vk::DescriptorSetLayoutBinding],
+) -> Vec<BindingDescription> {
+    let mut descriptions: Vec<BindingDescription> = bindings
+        .iter()
+        .map(|b| BindingDescription::from_raw(b))
+        .collect();
+    descriptions.sort_unstable_by_key(|b| b.index);
+    descriptions
 }
 
 struct UniqueDescriptorSetLayout {
@@ -87,6 +273,8 @@ struct UniqueDescriptorSetLayout {
     device: Device,
     samplers: Vec<Sampler>,
     binding_count: u32,
+    bindings: Vec<BindingDescription>,
+    flags: vk::DescriptorSetLayoutCreateFlags,
 }
 
 impl UniqueDescriptorSetLayout {
@@ -96,18 +284,30 @@ impl UniqueDescriptorSetLayout {
         samplers: Vec<Sampler>,
     ) -> CreateDescriptorSetLayoutResult<Self> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Creating descriptor set layout with {} bindings",
             create_info.binding_count
         );
         let handle = device
             .handle()
-            .create_descriptor_set_layout(create_info, None)?;
+            .create_descriptor_set_layout(create_info, device.allocation_callbacks())?;
+
+        let bindings = if create_info.binding_count == 0 {
+            Vec::new()
+        } else {
+            binding_descriptions(std::slice::from_raw_parts(
+                create_info.p_bindings,
+                create_info.binding_count as usize,
+            ))
+        };
 
         Ok(Self {
             handle,
             device,
             samplers,
             binding_count: create_info.binding_count,
+            bindings,
+            flags: create_info.flags,
         })
     }
 
@@ -126,22 +326,39 @@ impl UniqueDescriptorSetLayout {
     pub fn binding_count(&self) -> u32 {
         self.binding_count
     }
+
+    pub fn bindings(&self) -> &[BindingDescription] {
+        &self.bindings
+    }
+
+    pub fn flags(&self) -> vk::DescriptorSetLayoutCreateFlags {
+        self.flags
+    }
 }
 
 impl Drop for UniqueDescriptorSetLayout {
     fn drop(&mut self) {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Destroying descriptor set layout with {} bindings",
             self.binding_count()
         );
         unsafe {
             self.device
                 .handle()
-                .destroy_descriptor_set_layout(*self.handle(), None)
+                .destroy_descriptor_set_layout(*self.handle(), self.device.allocation_callbacks())
         }
     }
 }
 
+impl fmt::Debug for UniqueDescriptorSetLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DescriptorSetLayout")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
 impl Eq for UniqueDescriptorSetLayout {}
 
 impl PartialEq for UniqueDescriptorSetLayout {
@@ -150,11 +367,21 @@ impl PartialEq for UniqueDescriptorSetLayout {
     }
 }
 
+impl Hash for UniqueDescriptorSetLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 pub type CreateDescriptorSetLayoutResult<T> = Result<T, CreateDescriptorSetLayoutError>;
 
 #[derive(Debug)]
 pub enum CreateDescriptorSetLayoutError {
     VkError(vk::Result),
+    DeviceLost,
+    DuplicateBinding(u32),
+    TooManyDescriptors(ExceededLimit),
+    PushDescriptorNotEnabled,
 }
 
 impl Error for CreateDescriptorSetLayoutError {}
@@ -162,13 +389,233 @@ impl Error for CreateDescriptorSetLayoutError {}
 impl fmt::Display for CreateDescriptorSetLayoutError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::VkError(e) => write!(f, "Can't create descriptor set layout: {}", e),
+            Self::VkError(e) => write!(
+                f,
+                "Can't create descriptor set layout: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create descriptor set layout: device lost"),
+            Self::DuplicateBinding(index) => write!(
+                f,
+                "Can't create descriptor set layout: binding index {} is used more than once",
+                index
+            ),
+            Self::TooManyDescriptors(exceeded) => write!(
+                f,
+                "Can't create descriptor set layout: {} descriptors exceed {} limit of {}",
+                exceeded.count, exceeded.limit_name, exceeded.limit
+            ),
+            Self::PushDescriptorNotEnabled => write!(
+                f,
+                "Can't create push descriptor set layout: VK_KHR_push_descriptor is not enabled"
+            ),
         }
     }
 }
 
 impl From<vk::Result> for CreateDescriptorSetLayoutError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device;
+    use binding::BindingDescriptorType;
+
+    fn binding(index: u32, ty: vk::DescriptorType, count: u32) -> vk::DescriptorSetLayoutBinding {
+        vk::DescriptorSetLayoutBinding {
+            binding: index,
+            descriptor_type: ty,
+            descriptor_count: count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn duplicate_binding_index_is_detected() {
+        let bindings = [
+            binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1),
+            binding(1, vk::DescriptorType::STORAGE_BUFFER, 1),
+            binding(0, vk::DescriptorType::SAMPLER, 1),
+        ];
+        assert_eq!(duplicate_binding(&bindings), Some(0));
+        assert_eq!(duplicate_binding(&bindings[..2]), None);
+    }
+
+    #[test]
+    fn descriptor_counts_are_checked_against_limits() {
+        let limits = vk::PhysicalDeviceLimits {
+            max_descriptor_set_samplers: 4,
+            max_descriptor_set_sampled_images: 4,
+            max_descriptor_set_uniform_buffers: 8,
+            max_descriptor_set_uniform_buffers_dynamic: 2,
+            ..Default::default()
+        };
+
+        let fits = [
+            binding(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+            binding(1, vk::DescriptorType::SAMPLED_IMAGE, 2),
+            binding(2, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 2),
+        ];
+        assert_eq!(exceeded_limit(&fits, &limits), None);
+
+        let too_many_images = [
+            binding(0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 2),
+            binding(1, vk::DescriptorType::UNIFORM_TEXEL_BUFFER, 3),
+        ];
+        assert_eq!(
+            exceeded_limit(&too_many_images, &limits),
+            Some(ExceededLimit {
+                limit_name: "max_descriptor_set_sampled_images",
+                count: 5,
+                limit: 4,
+            })
+        );
+
+        let too_many_dynamic = [binding(0, vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 3)];
+        assert_eq!(
+            exceeded_limit(&too_many_dynamic, &limits).map(|e| e.limit_name),
+            Some("max_descriptor_set_uniform_buffers_dynamic")
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn push_descriptor_requires_extension() {
+        let (device, _queue, _pool) = compute_device();
+        match DescriptorSetLayoutBuilder::default()
+            .with_push_descriptor()
+            .build(device)
+        {
+            Err(CreateDescriptorSetLayoutError::PushDescriptorNotEnabled) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn identical_bindings_are_compatible_in_any_order() {
+        let flags = vk::DescriptorSetLayoutCreateFlags::empty();
+        let uniform = binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1);
+        let storage = binding(1, vk::DescriptorType::STORAGE_BUFFER, 2);
+
+        let (bindings, reordered, other_type) = unsafe {
+            (
+                binding_descriptions(&[uniform, storage]),
+                binding_descriptions(&[storage, uniform]),
+                binding_descriptions(&[uniform, binding(1, vk::DescriptorType::SAMPLED_IMAGE, 2)]),
+            )
+        };
+        assert!(identically_defined(flags, &bindings, flags, &reordered));
+        assert!(!identically_defined(flags, &bindings, flags, &other_type));
+        assert!(!identically_defined(
+            flags,
+            &bindings,
+            flags,
+            &bindings[..1]
+        ));
+
+        let push = vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR;
+        assert!(!identically_defined(flags, &bindings, push, &bindings));
+    }
+
+    #[test]
+    fn stage_flags_affect_compatibility() {
+        let flags = vk::DescriptorSetLayoutCreateFlags::empty();
+        let compute = vk::DescriptorSetLayoutBinding {
+            stage_flags: vk::ShaderStageFlags::COMPUTE,
+            ..binding(0, vk::DescriptorType::UNIFORM_BUFFER, 1)
+        };
+        let fragment = vk::DescriptorSetLayoutBinding {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            ..compute
+        };
+
+        let (compute, fragment) = unsafe {
+            (
+                binding_descriptions(&[compute]),
+                binding_descriptions(&[fragment]),
+            )
+        };
+        assert!(!identically_defined(flags, &compute, flags, &fragment));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn layouts_with_same_bindings_are_compatible() {
+        let (device, _queue, _pool) = compute_device();
+        let build = |ty: BindingDescriptorType| {
+            DescriptorSetLayoutBuilder::default()
+                .add_binding(BindingInfo::new(0, ty, 1, vk::ShaderStageFlags::COMPUTE))
+                .build(device.clone())
+                .unwrap()
+        };
+
+        let first = build(BindingDescriptorType::UniformBuffer);
+        let second = build(BindingDescriptorType::UniformBuffer);
+        let other = build(BindingDescriptorType::StorageBuffer);
+
+        assert!(!first.same_instance(&second));
+        assert!(first.is_compatible_with(&second));
+        assert!(!first.is_compatible_with(&other));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn chained_bindings_are_added() {
+        let (device, _queue, _pool) = compute_device();
+        let stages = vk::ShaderStageFlags::COMPUTE;
+        let layout = DescriptorSetLayoutBuilder::default()
+            .add_binding(BindingInfo::new(
+                0,
+                BindingDescriptorType::UniformBuffer,
+                1,
+                stages,
+            ))
+            .add_binding(BindingInfo::new(
+                1,
+                BindingDescriptorType::StorageBuffer,
+                2,
+                stages,
+            ))
+            .build(device)
+            .unwrap();
+
+        assert_eq!(layout.binding_count(), 2);
+        let bindings = layout.bindings();
+        assert_eq!(bindings[0].index, 0);
+        assert_eq!(bindings[1].index, 1);
+        assert_eq!(bindings[1].descriptor_count, 2);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn build_rejects_duplicate_binding() {
+        let (device, _queue, _pool) = compute_device();
+        let stages = vk::ShaderStageFlags::COMPUTE;
+        match DescriptorSetLayoutBuilder::default()
+            .add_binding(BindingInfo::new(
+                0,
+                BindingDescriptorType::UniformBuffer,
+                1,
+                stages,
+            ))
+            .add_binding(BindingInfo::new(
+                0,
+                BindingDescriptorType::StorageBuffer,
+                1,
+                stages,
+            ))
+            .build(device)
+        {
+            Err(CreateDescriptorSetLayoutError::DuplicateBinding(0)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
     }
 }
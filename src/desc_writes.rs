@@ -0,0 +1,292 @@
+use crate::device::Device;
+use ash::extensions::khr::PushDescriptor;
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+/// Accumulates descriptor writes and flushes them with single `vkUpdateDescriptorSets` call.
+/// Owns all buffer, image and texel buffer view infos until `apply`.
+#[derive(Default)]
+pub struct DescriptorWrites {
+    writes: Vec<Write>,
+}
+
+impl DescriptorWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_buffers(
+        mut self,
+        dst: DescriptorDst,
+        descriptor_type: vk::DescriptorType,
+        infos: Vec<vk::DescriptorBufferInfo>,
+    ) -> Self {
+        self.writes.push(Write {
+            dst,
+            descriptor_type,
+            infos: WriteInfos::Buffers(infos),
+        });
+        self
+    }
+
+    pub fn write_images(
+        mut self,
+        dst: DescriptorDst,
+        descriptor_type: vk::DescriptorType,
+        infos: Vec<vk::DescriptorImageInfo>,
+    ) -> Self {
+        self.writes.push(Write {
+            dst,
+            descriptor_type,
+            infos: WriteInfos::Images(infos),
+        });
+        self
+    }
+
+    pub fn write_texel_buffer_views(
+        mut self,
+        dst: DescriptorDst,
+        descriptor_type: vk::DescriptorType,
+        views: Vec<vk::BufferView>,
+    ) -> Self {
+        self.writes.push(Write {
+            dst,
+            descriptor_type,
+            infos: WriteInfos::TexelBufferViews(views),
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Performs all accumulated writes in single call.
+    ///
+    /// # Safety
+    /// Destination sets and all handles in infos must be valid and created from `device`.
+    /// Destination sets must not be in use by pending command buffers.
+    pub unsafe fn apply(&self, device: &Device) {
+        if self.writes.is_empty() {
+            return;
+        }
+
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Updating descriptor sets with {} writes",
+            self.writes.len()
+        );
+        let raw_writes: Vec<_> = self.writes.iter().map(Write::raw).collect();
+        device.handle().update_descriptor_sets(&raw_writes, &[]);
+    }
+}
+
+/// Records pushing of all `writes` to `set` of pipeline `layout` into `command_buffer`,
+/// so no descriptor pool is needed. Destination sets of `writes` are ignored.
+///
+/// # Safety
+/// `device` must be created with `VK_KHR_push_descriptor` extension enabled.
+/// `command_buffer` must be in recording state. Set layout #`set` of `layout` must be built
+/// `with_push_descriptor` and all handles in infos must be alive until execution is completed.
+pub unsafe fn push_descriptor_set(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    bind_point: vk::PipelineBindPoint,
+    layout: vk::PipelineLayout,
+    set: u32,
+    writes: &DescriptorWrites,
+) {
+    if writes.is_empty() {
+        return;
+    }
+
+    let raw_writes: Vec<_> = writes.writes.iter().map(Write::raw).collect();
+    let loader = PushDescriptor::new(device.instance().handle(), device.handle());
+    loader.cmd_push_descriptor_set(command_buffer, bind_point, layout, set, &raw_writes);
+}
+
+/// Place in descriptor set to write to.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DescriptorDst {
+    pub set: vk::DescriptorSet,
+    pub binding: u32,
+    pub array_element: u32,
+}
+
+impl DescriptorDst {
+    pub fn new(set: vk::DescriptorSet, binding: u32) -> Self {
+        Self {
+            set,
+            binding,
+            array_element: 0,
+        }
+    }
+
+    pub fn with_array_element(mut self, array_element: u32) -> Self {
+        self.array_element = array_element;
+        self
+    }
+}
+
+struct Write {
+    dst: DescriptorDst,
+    descriptor_type: vk::DescriptorType,
+    infos: WriteInfos,
+}
+
+impl Write {
+    fn raw(&self) -> vk::WriteDescriptorSet {
+        let mut raw = vk::WriteDescriptorSet {
+            dst_set: self.dst.set,
+            dst_binding: self.dst.binding,
+            dst_array_element: self.dst.array_element,
+            descriptor_type: self.descriptor_type,
+            ..Default::default()
+        };
+
+        match &self.infos {
+            WriteInfos::Buffers(infos) => {
+                raw.descriptor_count = infos.len() as u32;
+                raw.p_buffer_info = infos.as_ptr();
+            }
+            WriteInfos::Images(infos) => {
+                raw.descriptor_count = infos.len() as u32;
+                raw.p_image_info = infos.as_ptr();
+            }
+            WriteInfos::TexelBufferViews(views) => {
+                raw.descriptor_count = views.len() as u32;
+                raw.p_texel_buffer_view = views.as_ptr();
+            }
+        }
+
+        raw
+    }
+}
+
+enum WriteInfos {
+    Buffers(Vec<vk::DescriptorBufferInfo>),
+    Images(Vec<vk::DescriptorImageInfo>),
+    TexelBufferViews(Vec<vk::BufferView>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferBuilder;
+    use crate::command_buffer::OneTimeSubmit;
+    use crate::desc_set_layout::binding::{BindingDescriptorType, BindingInfo};
+    use crate::desc_set_layout::DescriptorSetLayoutBuilder;
+    use crate::test_utils::compute_device_with;
+    use ash::vk::Handle;
+
+    #[test]
+    fn buffer_and_image_writes_are_batched() {
+        let set = vk::DescriptorSet::from_raw(1);
+        let buffer_infos = vec![
+            vk::DescriptorBufferInfo {
+                buffer: vk::Buffer::from_raw(2),
+                offset: 0,
+                range: vk::WHOLE_SIZE,
+            };
+            2
+        ];
+        let image_infos = vec![vk::DescriptorImageInfo {
+            sampler: vk::Sampler::null(),
+            image_view: vk::ImageView::from_raw(3),
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        }];
+
+        let writes = DescriptorWrites::new()
+            .write_buffers(
+                DescriptorDst::new(set, 0),
+                vk::DescriptorType::UNIFORM_BUFFER,
+                buffer_infos,
+            )
+            .write_images(
+                DescriptorDst::new(set, 1).with_array_element(2),
+                vk::DescriptorType::SAMPLED_IMAGE,
+                image_infos,
+            );
+        assert_eq!(writes.len(), 2);
+
+        let raw: Vec<_> = writes.writes.iter().map(Write::raw).collect();
+
+        assert_eq!(raw[0].dst_set, set);
+        assert_eq!(raw[0].dst_binding, 0);
+        assert_eq!(raw[0].descriptor_type, vk::DescriptorType::UNIFORM_BUFFER);
+        assert_eq!(raw[0].descriptor_count, 2);
+        assert!(raw[0].p_image_info.is_null());
+        assert!(raw[0].p_texel_buffer_view.is_null());
+        let buffers = unsafe { std::slice::from_raw_parts(raw[0].p_buffer_info, 2) };
+        assert!(buffers.iter().all(|b| b.buffer == vk::Buffer::from_raw(2)));
+
+        assert_eq!(raw[1].dst_binding, 1);
+        assert_eq!(raw[1].dst_array_element, 2);
+        assert_eq!(raw[1].descriptor_type, vk::DescriptorType::SAMPLED_IMAGE);
+        assert_eq!(raw[1].descriptor_count, 1);
+        assert!(raw[1].p_buffer_info.is_null());
+        assert!(raw[1].p_texel_buffer_view.is_null());
+        let image = unsafe { &*raw[1].p_image_info };
+        assert_eq!(image.image_view, vk::ImageView::from_raw(3));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device with VK_KHR_push_descriptor"]
+    fn descriptors_are_pushed_without_pool() {
+        let (device, queue, pool) = compute_device_with(|builder| {
+            builder.with_extensions(vec![PushDescriptor::name().to_owned()])
+        });
+        let set_layout = DescriptorSetLayoutBuilder::default()
+            .add_binding(BindingInfo::new(
+                0,
+                BindingDescriptorType::StorageBuffer,
+                1,
+                vk::ShaderStageFlags::COMPUTE,
+            ))
+            .with_push_descriptor()
+            .build(device.clone())
+            .unwrap();
+        let buffer = BufferBuilder::default()
+            .with_size(16)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .build(device.clone(), &[])
+            .unwrap();
+
+        let writes = DescriptorWrites::new().write_buffers(
+            DescriptorDst::new(vk::DescriptorSet::null(), 0),
+            vk::DescriptorType::STORAGE_BUFFER,
+            vec![buffer.descriptor_info()],
+        );
+
+        unsafe {
+            let layout_info = vk::PipelineLayoutCreateInfo {
+                set_layout_count: 1,
+                p_set_layouts: set_layout.handle(),
+                ..Default::default()
+            };
+            let layout = device
+                .handle()
+                .create_pipeline_layout(&layout_info, device.allocation_callbacks())
+                .unwrap();
+
+            let one_time_submit = OneTimeSubmit::begin(&pool, &device).unwrap();
+            push_descriptor_set(
+                &device,
+                one_time_submit.recorder(),
+                vk::PipelineBindPoint::COMPUTE,
+                layout,
+                0,
+                &writes,
+            );
+            one_time_submit.submit_and_wait(&queue).unwrap();
+
+            device
+                .handle()
+                .destroy_pipeline_layout(layout, device.allocation_callbacks());
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::desc_set_layout::DescriptorSetLayout;
+use crate::device::Device;
+use ash::version::DeviceV1_1;
+use ash::vk;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Builds template, updating descriptor sets with `layout`. Requires Vulkan 1.1 device.
+pub struct DescriptorUpdateTemplateBuilder {
+    layout: DescriptorSetLayout,
+    entries: Vec<vk::DescriptorUpdateTemplateEntry>,
+}
+
+impl DescriptorUpdateTemplateBuilder {
+    pub fn new(layout: DescriptorSetLayout) -> Self {
+        Self {
+            layout,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Entry describes where descriptor infos for a binding are placed in update data.
+    pub fn add_entry(mut self, entry: vk::DescriptorUpdateTemplateEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    pub fn with_entries(mut self, entries: Vec<vk::DescriptorUpdateTemplateEntry>) -> Self {
+        self.entries = entries;
+        self
+    }
+
+    pub fn build(self) -> CreateDescriptorUpdateTemplateResult<DescriptorUpdateTemplate> {
+        let create_info = vk::DescriptorUpdateTemplateCreateInfo {
+            descriptor_update_entry_count: self.entries.len() as u32,
+            p_descriptor_update_entries: self.entries.as_ptr(),
+            template_type: vk::DescriptorUpdateTemplateType::DESCRIPTOR_SET,
+            descriptor_set_layout: unsafe { *self.layout.handle() },
+            ..Default::default()
+        };
+
+        unsafe { DescriptorUpdateTemplate::new(&create_info, self.layout) }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DescriptorUpdateTemplate {
+    unique_template: Arc<UniqueDescriptorUpdateTemplate>,
+}
+
+impl DescriptorUpdateTemplate {
+    /// # Safety
+    /// `create_info.descriptor_set_layout` must be handle of `layout`.
+    pub unsafe fn new(
+        create_info: &vk::DescriptorUpdateTemplateCreateInfo,
+        layout: DescriptorSetLayout,
+    ) -> CreateDescriptorUpdateTemplateResult<Self> {
+        UniqueDescriptorUpdateTemplate::new(create_info, layout).map(|udut| Self {
+            unique_template: Arc::new(udut),
+        })
+    }
+
+    /// # Safety
+    /// todo
+    pub unsafe fn handle(&self) -> &vk::DescriptorUpdateTemplate {
+        self.unique_template.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_template)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_template, &other.unique_template)
+    }
+
+    pub fn layout(&self) -> &DescriptorSetLayout {
+        self.unique_template.layout()
+    }
+
+    pub fn device(&self) -> &Device {
+        self.unique_template.layout().device()
+    }
+
+    /// Updates descriptors of `set` with infos, placed in `data` as described by template entries.
+    ///
+    /// # Safety
+    /// `set` must be allocated with template's layout and not be in use by pending command buffers.
+    /// `data` must contain valid descriptor infos at offsets and strides of template entries.
+    pub unsafe fn update(&self, set: vk::DescriptorSet, data: &[u8]) {
+        self.device().handle().update_descriptor_set_with_template(
+            set,
+            *self.handle(),
+            data.as_ptr().cast(),
+        )
+    }
+}
+
+struct UniqueDescriptorUpdateTemplate {
+    handle: vk::DescriptorUpdateTemplate,
+    layout: DescriptorSetLayout,
+    entry_count: u32,
+}
+
+impl UniqueDescriptorUpdateTemplate {
+    pub unsafe fn new(
+        create_info: &vk::DescriptorUpdateTemplateCreateInfo,
+        layout: DescriptorSetLayout,
+    ) -> CreateDescriptorUpdateTemplateResult<Self> {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Creating descriptor update template with {} entries",
+            create_info.descriptor_update_entry_count
+        );
+        let device = layout.device();
+        let handle = device
+            .handle()
+            .create_descriptor_update_template(create_info, device.allocation_callbacks())?;
+        Ok(Self {
+            handle,
+            layout,
+            entry_count: create_info.descriptor_update_entry_count,
+        })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::DescriptorUpdateTemplate {
+        &self.handle
+    }
+
+    pub fn layout(&self) -> &DescriptorSetLayout {
+        &self.layout
+    }
+}
+
+impl Drop for UniqueDescriptorUpdateTemplate {
+    fn drop(&mut self) {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Destroying descriptor update template with {} entries",
+            self.entry_count
+        );
+        let device = self.layout.device();
+        unsafe {
+            device
+                .handle()
+                .destroy_descriptor_update_template(self.handle, device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueDescriptorUpdateTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DescriptorUpdateTemplate")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Eq for UniqueDescriptorUpdateTemplate {}
+
+impl PartialEq for UniqueDescriptorUpdateTemplate {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueDescriptorUpdateTemplate {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+pub type CreateDescriptorUpdateTemplateResult<T> = Result<T, CreateDescriptorUpdateTemplateError>;
+
+#[derive(Debug)]
+pub enum CreateDescriptorUpdateTemplateError {
+    VkError(vk::Result),
+    DeviceLost,
+}
+
+impl Error for CreateDescriptorUpdateTemplateError {}
+
+impl fmt::Display for CreateDescriptorUpdateTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't create descriptor update template: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create descriptor update template: device lost"),
+        }
+    }
+}
+
+impl From<vk::Result> for CreateDescriptorUpdateTemplateError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferBuilder;
+    use crate::desc_set_layout::binding::{BindingDescriptorType, BindingInfo};
+    use crate::desc_set_layout::DescriptorSetLayoutBuilder;
+    use crate::test_utils::compute_device;
+    use ash::version::DeviceV1_0;
+    use std::mem::size_of;
+
+    #[test]
+    #[ignore = "requires Vulkan 1.1 device"]
+    fn uniform_buffer_is_written_by_template() {
+        let (device, _queue, _pool) = compute_device();
+        let layout = DescriptorSetLayoutBuilder::default()
+            .add_binding(BindingInfo::new(
+                0,
+                BindingDescriptorType::UniformBuffer,
+                1,
+                vk::ShaderStageFlags::COMPUTE,
+            ))
+            .build(device.clone())
+            .unwrap();
+        let template = DescriptorUpdateTemplateBuilder::new(layout.clone())
+            .add_entry(vk::DescriptorUpdateTemplateEntry {
+                dst_binding: 0,
+                dst_array_element: 0,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                offset: 0,
+                stride: size_of::<vk::DescriptorBufferInfo>(),
+            })
+            .build()
+            .unwrap();
+        assert!(template.layout().same_instance(&layout));
+
+        let (buffer, _memory) = BufferBuilder::default()
+            .with_size(64)
+            .with_usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .build_with_memory(device.clone(), &[], vk::MemoryPropertyFlags::empty())
+            .unwrap();
+        let info = buffer.descriptor_info();
+
+        unsafe {
+            let pool_size = vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: 1,
+            };
+            let pool_info = vk::DescriptorPoolCreateInfo {
+                max_sets: 1,
+                pool_size_count: 1,
+                p_pool_sizes: &pool_size,
+                ..Default::default()
+            };
+            let pool = device
+                .handle()
+                .create_descriptor_pool(&pool_info, device.allocation_callbacks())
+                .unwrap();
+            let allocate_info = vk::DescriptorSetAllocateInfo {
+                descriptor_pool: pool,
+                descriptor_set_count: 1,
+                p_set_layouts: layout.handle(),
+                ..Default::default()
+            };
+            let set = device
+                .handle()
+                .allocate_descriptor_sets(&allocate_info)
+                .unwrap()[0];
+
+            let data = std::slice::from_raw_parts(
+                (&info as *const vk::DescriptorBufferInfo).cast::<u8>(),
+                size_of::<vk::DescriptorBufferInfo>(),
+            );
+            template.update(set, data);
+
+            device
+                .handle()
+                .destroy_descriptor_pool(pool, device.allocation_callbacks());
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use ash::vk;
+
+macro_rules! unsupported_features {
+    ($requested:expr, $available:expr, $($feature:ident),* $(,)?) => {{
+        let mut unsupported = Vec::new();
+        $(
+            if $requested.$feature == vk::TRUE && $available.$feature != vk::TRUE {
+                unsupported.push(stringify!($feature));
+            }
+        )*
+        unsupported
+    }};
+}
+
+/// Returns names of `requested` features, that are not enabled in `available` features.
+pub fn unsupported_features(
+    requested: &vk::PhysicalDeviceFeatures,
+    available: &vk::PhysicalDeviceFeatures,
+) -> Vec<&'static str> {
+    unsupported_features!(
+        requested,
+        available,
+        robust_buffer_access,
+        full_draw_index_uint32,
+        image_cube_array,
+        independent_blend,
+        geometry_shader,
+        tessellation_shader,
+        sample_rate_shading,
+        dual_src_blend,
+        logic_op,
+        multi_draw_indirect,
+        draw_indirect_first_instance,
+        depth_clamp,
+        depth_bias_clamp,
+        fill_mode_non_solid,
+        depth_bounds,
+        wide_lines,
+        large_points,
+        alpha_to_one,
+        multi_viewport,
+        sampler_anisotropy,
+        texture_compression_etc2,
+        texture_compression_astc_ldr,
+        texture_compression_bc,
+        occlusion_query_precise,
+        pipeline_statistics_query,
+        vertex_pipeline_stores_and_atomics,
+        fragment_stores_and_atomics,
+        shader_tessellation_and_geometry_point_size,
+        shader_image_gather_extended,
+        shader_storage_image_extended_formats,
+        shader_storage_image_multisample,
+        shader_storage_image_read_without_format,
+        shader_storage_image_write_without_format,
+        shader_uniform_buffer_array_dynamic_indexing,
+        shader_sampled_image_array_dynamic_indexing,
+        shader_storage_buffer_array_dynamic_indexing,
+        shader_storage_image_array_dynamic_indexing,
+        shader_clip_distance,
+        shader_cull_distance,
+        shader_float64,
+        shader_int64,
+        shader_int16,
+        shader_resource_residency,
+        shader_resource_min_lod,
+        sparse_binding,
+        sparse_residency_buffer,
+        sparse_residency_image2_d,
+        sparse_residency_image3_d,
+        sparse_residency2_samples,
+        sparse_residency4_samples,
+        sparse_residency8_samples,
+        sparse_residency16_samples,
+        sparse_residency_aliased,
+        variable_multisample_rate,
+        inherited_queries,
+    )
+}
+
+/// Links `feature_structs` to `p_next` chain, that starts from returned structure.
+/// Returned structure must not outlive `feature_structs`.
+pub fn chain_features2(
+    features: vk::PhysicalDeviceFeatures,
+    feature_structs: &mut [Box<dyn vk::ExtendsDeviceCreateInfo>],
+) -> vk::PhysicalDeviceFeatures2 {
+    vk::PhysicalDeviceFeatures2 {
+        p_next: unsafe { crate::link_p_next(feature_structs) },
+        features,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_features_are_named() {
+        let available = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            shader_int64: vk::TRUE,
+            ..Default::default()
+        };
+        let requested = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            geometry_shader: vk::TRUE,
+            inherited_queries: vk::TRUE,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            unsupported_features(&requested, &available),
+            vec!["geometry_shader", "inherited_queries"]
+        );
+        assert!(unsupported_features(&available, &available).is_empty());
+        assert!(unsupported_features(&Default::default(), &Default::default()).is_empty());
+    }
+
+    #[test]
+    fn any_device_create_info_extension_is_chained() {
+        let mut feature_structs: Vec<Box<dyn vk::ExtendsDeviceCreateInfo>> = vec![
+            Box::new(vk::PhysicalDeviceVulkan12Features {
+                timeline_semaphore: vk::TRUE,
+                ..Default::default()
+            }),
+            Box::new(vk::PhysicalDeviceShaderClockFeaturesKHR::default()),
+        ];
+        let features = vk::PhysicalDeviceFeatures {
+            sampler_anisotropy: vk::TRUE,
+            ..Default::default()
+        };
+        let features2 = chain_features2(features, &mut feature_structs);
+        assert_eq!(features2.features.sampler_anisotropy, vk::TRUE);
+
+        let vulkan12 = features2.p_next as *const vk::PhysicalDeviceVulkan12Features;
+        let clock =
+            unsafe { (*vulkan12).p_next } as *const vk::PhysicalDeviceShaderClockFeaturesKHR;
+        unsafe {
+            assert_eq!((*vulkan12).timeline_semaphore, vk::TRUE);
+            assert_eq!(
+                (*clock).s_type,
+                vk::StructureType::PHYSICAL_DEVICE_SHADER_CLOCK_FEATURES_KHR
+            );
+            assert!((*clock).p_next.is_null());
+        }
+    }
+}
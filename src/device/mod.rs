@@ -1,20 +1,26 @@
+pub mod features;
 pub mod pdevice_selectors;
 use crate::device::pdevice_selectors::PhysicalDeviceSelector;
 use crate::instance::Instance;
-use crate::{get_c_str_pointers, raw_name_to_c_string};
+use crate::{c_strings_from_pointers, get_c_str_pointers, raw_name_to_c_string, ContainRawVkName};
+use ash::prelude::VkResult;
 use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk;
 use pdevice_selectors::{PhysicalDeviceError, PhysicalDeviceInfo};
 use std::error::Error;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::fmt;
 use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 pub struct DeviceBuilder {
     pdevice_selector: Box<dyn PhysicalDeviceSelector>,
     layers: Vec<CString>,
     extensions: Vec<CString>,
+    features: Option<vk::PhysicalDeviceFeatures>,
+    feature_structs: Vec<Box<dyn vk::ExtendsDeviceCreateInfo>>,
 }
 
 impl DeviceBuilder {
@@ -23,6 +29,8 @@ impl DeviceBuilder {
             pdevice_selector,
             layers: vec![],
             extensions: vec![],
+            features: None,
+            feature_structs: vec![],
         }
     }
 
@@ -36,6 +44,23 @@ impl DeviceBuilder {
         self
     }
 
+    /// Overrides features, provided by physical device selector.
+    pub fn with_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.features = Some(features);
+        self
+    }
+
+    /// Adds extension feature structure (e.g. `vk::PhysicalDeviceVulkan12Features`).
+    /// If any is added, features are passed through `vk::PhysicalDeviceFeatures2` chain.
+    /// Builder owns `feature_struct` until device is built. `p_next` of it is overwritten.
+    pub fn with_feature_struct<T: vk::ExtendsDeviceCreateInfo + 'static>(
+        mut self,
+        feature_struct: T,
+    ) -> Self {
+        self.feature_structs.push(Box::new(feature_struct));
+        self
+    }
+
     pub fn build(self, instance: Instance) -> Result<Device, CreateDeviceError> {
         let mut create_info = vk::DeviceCreateInfo::default();
 
@@ -50,18 +75,72 @@ impl DeviceBuilder {
         let selector = self.pdevice_selector;
         let pdevice_info = selector(&instance)?;
 
+        let unsupported_extensions =
+            unsupported_extensions(&instance, pdevice_info.pdevice, &self.extensions)?;
+        if !unsupported_extensions.is_empty() {
+            return Err(CreateDeviceError::UnsupportedExtensions(
+                unsupported_extensions,
+            ));
+        }
+
         let mut queues_info_builder = QueueCreateInfosBuilder::new(pdevice_info.queues_info.iter());
         let queue_infos = queues_info_builder.build();
         create_info.p_queue_create_infos = queue_infos.as_ptr();
         create_info.queue_create_info_count = queue_infos.len() as u32;
 
-        create_info.p_enabled_features = &pdevice_info.physical_device_features;
+        let enabled_features = self
+            .features
+            .unwrap_or(pdevice_info.physical_device_features);
+        let available_features = unsafe {
+            instance
+                .handle()
+                .get_physical_device_features(pdevice_info.pdevice)
+        };
+        let unsupported_features =
+            features::unsupported_features(&enabled_features, &available_features);
+        if !unsupported_features.is_empty() {
+            return Err(CreateDeviceError::UnsupportedFeatures(unsupported_features));
+        }
+
+        let mut feature_structs = self.feature_structs;
+        let features2 = features::chain_features2(enabled_features, &mut feature_structs);
+        if feature_structs.is_empty() {
+            create_info.p_enabled_features = &enabled_features;
+        } else {
+            create_info.p_next = (&features2 as *const vk::PhysicalDeviceFeatures2).cast();
+        }
 
         unsafe { Device::new(instance, pdevice_info, &create_info) }
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+fn unsupported_extensions(
+    instance: &Instance,
+    pdevice: vk::PhysicalDevice,
+    extensions: &[CString],
+) -> Result<Vec<CString>, CreateDeviceError> {
+    let available: Vec<CString> = unsafe {
+        instance
+            .handle()
+            .enumerate_device_extension_properties(pdevice)?
+    }
+    .into_iter()
+    .map(|mut e| e.c_string_name())
+    .collect();
+
+    Ok(missing_extensions(extensions, &available))
+}
+
+/// Returns `requested` extensions, that aren't in `available` list.
+fn missing_extensions(requested: &[CString], available: &[CString]) -> Vec<CString> {
+    requested
+        .iter()
+        .filter(|e| !available.contains(e))
+        .cloned()
+        .collect()
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Device {
     unique_device: Arc<UniqueDevice>,
 }
@@ -81,14 +160,25 @@ impl Device {
         create_info: &vk::DeviceCreateInfo,
     ) -> Result<Self, CreateDeviceError> {
         let unique_device = Arc::new(UniqueDevice::new(instance, pdevice_info, create_info)?);
-        log::trace!("Device created: {}", unique_device);
+        log::trace!(target: crate::LOG_TARGET, "Device created: {}", unique_device);
         Ok(Self { unique_device })
     }
 
     /// # Safety
     /// TODO
     pub unsafe fn handle(&self) -> &ash::Device {
-        &self.unique_device.handle()
+        self.unique_device.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_device)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_device, &other.unique_device)
     }
 
     /// # Safety
@@ -102,14 +192,129 @@ impl Device {
     }
 
     pub fn instance(&self) -> &Instance {
-        &self.unique_device.instance()
+        self.unique_device.instance()
     }
+
+    /// Host memory allocation callbacks of the instance, used for all objects of the device.
+    pub fn allocation_callbacks(&self) -> Option<&vk::AllocationCallbacks> {
+        self.instance().allocation_callbacks()
+    }
+
+    /// Returns `true` if any operation of the crate observed `ERROR_DEVICE_LOST` on this device.
+    /// Lost device can't be used anymore and must be recreated.
+    pub fn is_lost(&self) -> bool {
+        self.unique_device.lost.load(Ordering::Relaxed)
+    }
+
+    /// Marks device lost if `result` is `ERROR_DEVICE_LOST`.
+    pub(crate) fn track_lost<T>(&self, result: VkResult<T>) -> VkResult<T> {
+        if let Err(vk::Result::ERROR_DEVICE_LOST) = result {
+            log::error!(target: crate::LOG_TARGET, "Device lost: {}", self);
+            self.unique_device.lost.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn enabled_extensions(&self) -> &[CString] {
+        self.unique_device.extensions()
+    }
+
+    pub fn is_extension_enabled(&self, name: &CStr) -> bool {
+        self.unique_device
+            .extensions()
+            .iter()
+            .any(|e| e.as_c_str() == name)
+    }
+
+    /// Returns index of first memory type, that is allowed by `type_bits`
+    /// (e.g. `vk::MemoryRequirements::memory_type_bits`) and has all `properties`.
+    pub fn find_memory_type_index(
+        &self,
+        type_bits: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Option<u32> {
+        let memory_properties = unsafe {
+            self.instance()
+                .handle()
+                .get_physical_device_memory_properties(*self.pdevice())
+        };
+
+        memory_properties.memory_types[..memory_properties.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .find(|(index, memory_type)| {
+                type_bits & (1 << index) != 0 && memory_type.property_flags.contains(properties)
+            })
+            .map(|(index, _)| index as u32)
+    }
+
+    pub fn limits(&self) -> vk::PhysicalDeviceLimits {
+        unsafe {
+            self.instance()
+                .handle()
+                .get_physical_device_properties(*self.pdevice())
+                .limits
+        }
+    }
+
+    pub fn compute_limits(&self) -> ComputeLimits {
+        let limits = self.limits();
+        ComputeLimits {
+            max_work_group_count: limits.max_compute_work_group_count,
+            max_work_group_size: limits.max_compute_work_group_size,
+            max_work_group_invocations: limits.max_compute_work_group_invocations,
+        }
+    }
+
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        unsafe {
+            self.instance()
+                .handle()
+                .get_physical_device_format_properties(*self.pdevice(), format)
+        }
+    }
+
+    /// Returns `ERROR_FORMAT_NOT_SUPPORTED` if image with such parameters can't be created.
+    pub fn image_format_properties(
+        &self,
+        format: vk::Format,
+        image_type: vk::ImageType,
+        tiling: vk::ImageTiling,
+        usage: vk::ImageUsageFlags,
+        flags: vk::ImageCreateFlags,
+    ) -> VkResult<vk::ImageFormatProperties> {
+        unsafe {
+            self.instance()
+                .handle()
+                .get_physical_device_image_format_properties(
+                    *self.pdevice(),
+                    format,
+                    image_type,
+                    tiling,
+                    usage,
+                    flags,
+                )
+        }
+    }
+}
+
+/// Limits of compute dispatches.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ComputeLimits {
+    /// Max count of work groups in single dispatch per dimension.
+    pub max_work_group_count: [u32; 3],
+    /// Max size of work group per dimension.
+    pub max_work_group_size: [u32; 3],
+    /// Max total count of invocations in single work group.
+    pub max_work_group_invocations: u32,
 }
 
 struct UniqueDevice {
     instance: Instance,
     pdevice_info: PhysicalDeviceInfo,
     handle: ash::Device,
+    extensions: Vec<CString>,
+    lost: AtomicBool,
 }
 
 impl UniqueDevice {
@@ -118,16 +323,25 @@ impl UniqueDevice {
         pdevice_info: PhysicalDeviceInfo,
         create_info: &vk::DeviceCreateInfo,
     ) -> Result<Self, CreateDeviceError> {
-        log::trace!("Creating device");
+        log::trace!(target: crate::LOG_TARGET, "Creating device");
 
-        let handle = instance
-            .handle()
-            .create_device(pdevice_info.pdevice, create_info, None)?;
+        let handle = instance.handle().create_device(
+            pdevice_info.pdevice,
+            create_info,
+            instance.allocation_callbacks(),
+        )?;
+
+        let extensions = c_strings_from_pointers(
+            create_info.pp_enabled_extension_names,
+            create_info.enabled_extension_count,
+        );
 
         Ok(Self {
             instance,
             pdevice_info,
             handle,
+            extensions,
+            lost: AtomicBool::new(false),
         })
     }
 
@@ -142,12 +356,19 @@ impl UniqueDevice {
     pub fn instance(&self) -> &Instance {
         &self.instance
     }
+
+    pub fn extensions(&self) -> &[CString] {
+        &self.extensions
+    }
 }
 
 impl Drop for UniqueDevice {
     fn drop(&mut self) {
-        log::trace!("Destroying vulkan device");
-        unsafe { self.handle.destroy_device(None) }
+        log::trace!(target: crate::LOG_TARGET, "Destroying vulkan device");
+        unsafe {
+            self.handle
+                .destroy_device(self.instance.allocation_callbacks())
+        }
     }
 }
 
@@ -223,10 +444,19 @@ impl PartialEq for UniqueDevice {
     }
 }
 
+impl Hash for UniqueDevice {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.handle().hash(state)
+    }
+}
+
 #[derive(Debug)]
 pub enum CreateDeviceError {
     VkError(vk::Result),
+    DeviceLost,
     PhysicalDeviceError(PhysicalDeviceError),
+    UnsupportedExtensions(Vec<CString>),
+    UnsupportedFeatures(Vec<&'static str>),
 }
 
 impl Error for CreateDeviceError {}
@@ -234,17 +464,38 @@ impl Error for CreateDeviceError {}
 impl fmt::Display for CreateDeviceError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            CreateDeviceError::VkError(e) => write!(f, "Vulkan device creation failed: {}", e),
+            CreateDeviceError::VkError(e) => write!(
+                f,
+                "Vulkan device creation failed: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            CreateDeviceError::DeviceLost => {
+                write!(f, "Vulkan device creation failed: device lost")
+            }
             CreateDeviceError::PhysicalDeviceError(e) => {
                 write!(f, "Physical device selection failed: {}", e)
             }
+            CreateDeviceError::UnsupportedExtensions(extensions) => write!(
+                f,
+                "Selected physical device doesn't support extensions: {:?}",
+                extensions
+            ),
+            CreateDeviceError::UnsupportedFeatures(features) => write!(
+                f,
+                "Selected physical device doesn't support features: {:?}",
+                features
+            ),
         }
     }
 }
 
 impl From<vk::Result> for CreateDeviceError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
     }
 }
 
@@ -253,3 +504,139 @@ impl From<PhysicalDeviceError> for CreateDeviceError {
         Self::PhysicalDeviceError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::InstanceBuilder;
+    use crate::test_utils::{compute_device, compute_device_with};
+
+    fn names(names: &[&str]) -> Vec<CString> {
+        names.iter().map(|n| CString::new(*n).unwrap()).collect()
+    }
+
+    #[test]
+    fn missing_extensions_are_detected() {
+        let available = names(&["VK_KHR_swapchain", "VK_KHR_push_descriptor"]);
+
+        assert!(missing_extensions(&[], &available).is_empty());
+        assert!(missing_extensions(&names(&["VK_KHR_swapchain"]), &available).is_empty());
+        assert_eq!(
+            missing_extensions(
+                &names(&["VK_KHR_swapchain", "VK_NV_mesh_shader", "VK_EXT_foo"]),
+                &available
+            ),
+            names(&["VK_NV_mesh_shader", "VK_EXT_foo"])
+        );
+    }
+
+    fn instance() -> Instance {
+        let entry = ash::Entry::new().expect("Vulkan loader must be available");
+        InstanceBuilder::new(entry)
+            .with_api_version_clamped(1, 2, 0)
+            .build()
+            .expect("Instance must be created")
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn unknown_extension_is_rejected() {
+        match DeviceBuilder::new(Box::new(pdevice_selectors::any_compute))
+            .with_extensions(names(&["VK_LLW_unknown"]))
+            .build(instance())
+        {
+            Err(CreateDeviceError::UnsupportedExtensions(extensions)) => {
+                assert_eq!(extensions, names(&["VK_LLW_unknown"]))
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn unsupported_feature_is_rejected() {
+        let instance = instance();
+        let pdevice = pdevice_selectors::any_compute(&instance).unwrap().pdevice;
+        let available = unsafe { instance.handle().get_physical_device_features(pdevice) };
+        let requested = vk::PhysicalDeviceFeatures {
+            robust_buffer_access: vk::TRUE,
+            geometry_shader: vk::TRUE,
+            shader_float64: vk::TRUE,
+            sparse_residency_aliased: vk::TRUE,
+            variable_multisample_rate: vk::TRUE,
+            ..Default::default()
+        };
+        let expected = features::unsupported_features(&requested, &available);
+
+        let result = DeviceBuilder::new(Box::new(pdevice_selectors::any_compute))
+            .with_features(requested)
+            .build(instance);
+        match result {
+            Err(CreateDeviceError::UnsupportedFeatures(unsupported)) => {
+                assert_eq!(unsupported, expected)
+            }
+            Ok(_) => assert!(expected.is_empty()),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn mandatory_format_is_supported() {
+        let (device, _queue, _pool) = compute_device();
+        let format = vk::Format::R8G8B8A8_UNORM;
+
+        let properties = device.format_properties(format);
+        assert!(properties
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE));
+
+        let image_properties = device
+            .image_format_properties(
+                format,
+                vk::ImageType::TYPE_2D,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::SAMPLED,
+                vk::ImageCreateFlags::empty(),
+            )
+            .unwrap();
+        assert!(image_properties.max_extent.width >= 4096);
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn compute_limits_match_device_limits() {
+        let (device, _queue, _pool) = compute_device();
+        let limits = device.limits();
+        let compute_limits = device.compute_limits();
+
+        assert_eq!(
+            compute_limits.max_work_group_count,
+            limits.max_compute_work_group_count
+        );
+        assert_eq!(
+            compute_limits.max_work_group_size,
+            limits.max_compute_work_group_size
+        );
+        // Minimal values, guaranteed by Vulkan specification.
+        assert!(compute_limits.max_work_group_invocations >= 128);
+        assert!(compute_limits
+            .max_work_group_count
+            .iter()
+            .all(|&c| c >= 65535));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device with VK_KHR_maintenance1"]
+    fn enabled_extensions_are_reported() {
+        let maintenance1 = vk::KhrMaintenance1Fn::name();
+        let (device, _queue, _pool) =
+            compute_device_with(|builder| builder.with_extensions(vec![maintenance1.to_owned()]));
+
+        assert_eq!(device.enabled_extensions(), &[maintenance1.to_owned()][..]);
+        assert!(device.is_extension_enabled(maintenance1));
+        assert!(!device.is_extension_enabled(vk::KhrPushDescriptorFn::name()));
+        assert!(device.instance().enabled_extensions().is_empty());
+        assert!(device.instance().enabled_layers().is_empty());
+    }
+}
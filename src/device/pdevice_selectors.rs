@@ -1,27 +1,41 @@
 use crate::device::QueuesInfo;
 use crate::instance::Instance;
+use crate::raw_name_to_c_string;
+#[cfg(feature = "surface")]
+use crate::surface::Surface;
+use ash::prelude::VkResult;
 use ash::version::InstanceV1_0;
 use ash::vk;
 use ash::vk::{PhysicalDevice, QueueFlags};
 use std::error::Error;
 use std::fmt;
+use std::fmt::Write;
 
 pub type PhysicalDeviceResult = Result<PhysicalDeviceInfo, PhysicalDeviceError>;
 pub trait PhysicalDeviceSelector: FnOnce(&Instance) -> PhysicalDeviceResult {}
 impl<T: FnOnce(&Instance) -> PhysicalDeviceResult> PhysicalDeviceSelector for T {}
 
 pub fn any_graphics(instance: &Instance) -> PhysicalDeviceResult {
-    log::trace!("Selecting device with single graphics queue");
+    log::trace!(target: crate::LOG_TARGET, "Selecting device with single graphics queue");
     first_with_flags(instance, vk::QueueFlags::GRAPHICS)
 }
 
 pub fn any_compute(instance: &Instance) -> PhysicalDeviceResult {
-    log::trace!("Selecting device with single compute queue");
+    log::trace!(target: crate::LOG_TARGET, "Selecting device with single compute queue");
     first_with_flags(instance, vk::QueueFlags::COMPUTE)
 }
 
+/// Selects device with queue family supporting sparse binding and enables `sparse_binding` feature.
+pub fn any_sparse_binding(instance: &Instance) -> PhysicalDeviceResult {
+    log::trace!(target: crate::LOG_TARGET, "Selecting device with single sparse binding queue");
+    first_with_flags(instance, vk::QueueFlags::SPARSE_BINDING).map(|mut info| {
+        info.physical_device_features.sparse_binding = vk::TRUE;
+        info
+    })
+}
+
 pub fn first_with_flags(instance: &Instance, required_flags: QueueFlags) -> PhysicalDeviceResult {
-    let (pdevice, family_index) = first_device_with_family_flags(&instance, required_flags)?;
+    let (pdevice, family_index) = first_device_with_family_flags(instance, required_flags)?;
 
     Ok(PhysicalDeviceInfo {
         pdevice,
@@ -33,6 +47,158 @@ pub fn first_with_flags(instance: &Instance, required_flags: QueueFlags) -> Phys
     })
 }
 
+/// How to handle queue count, exceeding available in family.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum QueueCountPolicy {
+    /// Request as many queues, as family provides.
+    Clamp,
+    /// Select only families, providing all requested queues.
+    Strict,
+}
+
+/// Like `first_with_flags`, but requests `count` queues from selected family.
+pub fn first_with_flags_count(
+    instance: &Instance,
+    required_flags: QueueFlags,
+    count: u32,
+    policy: QueueCountPolicy,
+) -> PhysicalDeviceResult {
+    assert!(count > 0, "queue count must be non-zero");
+    log::trace!(
+        target: crate::LOG_TARGET,
+        "Selecting device with {} queues with flags {:?} ({:?})",
+        count,
+        required_flags,
+        policy
+    );
+
+    unsafe {
+        for pdevice in instance.handle().enumerate_physical_devices()? {
+            let queue_props = instance
+                .handle()
+                .get_physical_device_queue_family_properties(pdevice);
+
+            if let Some((family_index, count)) =
+                family_with_queue_count(&queue_props, required_flags, count, policy)
+            {
+                return Ok(PhysicalDeviceInfo {
+                    pdevice,
+                    physical_device_features: Default::default(),
+                    queues_info: vec![QueuesInfo {
+                        family_index,
+                        count,
+                    }],
+                });
+            }
+        }
+    }
+
+    Err(PhysicalDeviceError::NotFound(format!(
+        "Physical device with {} queues with flags {:?} not found",
+        count, required_flags
+    )))
+}
+
+/// Returns index of first family with `flags` and queue count to request from it.
+fn family_with_queue_count(
+    queue_props: &[vk::QueueFamilyProperties],
+    flags: vk::QueueFlags,
+    count: u32,
+    policy: QueueCountPolicy,
+) -> Option<(u32, u32)> {
+    queue_props
+        .iter()
+        .enumerate()
+        .filter(|(_, props)| props.queue_flags.contains(flags) && props.queue_count > 0)
+        .find_map(|(index, props)| match policy {
+            QueueCountPolicy::Clamp => Some((index as u32, count.min(props.queue_count))),
+            QueueCountPolicy::Strict if props.queue_count >= count => Some((index as u32, count)),
+            QueueCountPolicy::Strict => None,
+        })
+}
+
+/// Selects device with queue families, supporting graphics and presentation to `surface`.
+/// Single family, supporting both, is preferred. Else separate graphics and present families
+/// are selected.
+/// Instance must be created with surface extensions enabled.
+#[cfg(feature = "surface")]
+pub fn graphics_with_present(surface: Surface) -> Box<dyn PhysicalDeviceSelector> {
+    Box::new(move |instance: &Instance| {
+        log::trace!(target: crate::LOG_TARGET, "Selecting device with graphics and present queues");
+        unsafe {
+            for pdevice in instance.handle().enumerate_physical_devices()? {
+                if let Some(queues_info) = graphics_and_present_queues(instance, pdevice, &surface)?
+                {
+                    return Ok(PhysicalDeviceInfo {
+                        pdevice,
+                        physical_device_features: Default::default(),
+                        queues_info,
+                    });
+                }
+            }
+        }
+
+        Err(PhysicalDeviceError::NotFound(
+            "Physical device with graphics and present queues not found".into(),
+        ))
+    })
+}
+
+#[cfg(feature = "surface")]
+unsafe fn graphics_and_present_queues(
+    instance: &Instance,
+    pdevice: PhysicalDevice,
+    surface: &Surface,
+) -> Result<Option<Vec<QueuesInfo>>, PhysicalDeviceError> {
+    let loader = ash::extensions::khr::Surface::new(instance.entry(), instance.handle());
+    let queue_props = instance
+        .handle()
+        .get_physical_device_queue_family_properties(pdevice);
+
+    let mut graphics_family = None;
+    let mut present_family = None;
+    for (index, props) in queue_props.iter().enumerate() {
+        if props.queue_count == 0 {
+            continue;
+        }
+
+        let index = index as u32;
+        let graphics = props.queue_flags.contains(vk::QueueFlags::GRAPHICS);
+        let present =
+            loader.get_physical_device_surface_support(pdevice, index, *surface.handle())?;
+
+        if graphics && present {
+            return Ok(Some(vec![QueuesInfo {
+                family_index: index,
+                count: 1,
+            }]));
+        }
+
+        if graphics && graphics_family.is_none() {
+            graphics_family = Some(index);
+        }
+
+        if present && present_family.is_none() {
+            present_family = Some(index);
+        }
+    }
+
+    Ok(graphics_family
+        .zip(present_family)
+        .map(|(graphics, present)| {
+            vec![
+                QueuesInfo {
+                    family_index: graphics,
+                    count: 1,
+                },
+                QueuesInfo {
+                    family_index: present,
+                    count: 1,
+                },
+            ]
+        }))
+}
+
 fn first_device_with_family_flags(
     instance: &Instance,
     flags: vk::QueueFlags,
@@ -60,15 +226,149 @@ fn first_device_with_family_flags(
     )))
 }
 
+/// Describes all physical devices and their queue families. Useful to diagnose failed selection.
+pub fn dump_devices(instance: &Instance) -> VkResult<String> {
+    let pdevices = unsafe { instance.handle().enumerate_physical_devices()? };
+    if pdevices.is_empty() {
+        return Ok("No physical devices found".into());
+    }
+
+    let mut dump = String::new();
+    for (index, pdevice) in pdevices.into_iter().enumerate() {
+        let (mut props, queue_props) = unsafe {
+            (
+                instance.handle().get_physical_device_properties(pdevice),
+                instance
+                    .handle()
+                    .get_physical_device_queue_family_properties(pdevice),
+            )
+        };
+
+        writeln!(
+            dump,
+            "Device {}: {:?} ({:?}), API version {}.{}.{}",
+            index,
+            raw_name_to_c_string(&mut props.device_name),
+            props.device_type,
+            vk::version_major(props.api_version),
+            vk::version_minor(props.api_version),
+            vk::version_patch(props.api_version),
+        )
+        .unwrap();
+
+        for (family_index, family) in queue_props.iter().enumerate() {
+            writeln!(
+                dump,
+                "    Queue family {}: {} queues with flags {:?}",
+                family_index, family.queue_count, family.queue_flags
+            )
+            .unwrap();
+        }
+    }
+
+    Ok(dump)
+}
+
 pub struct PhysicalDeviceInfo {
     pub pdevice: PhysicalDevice,
     pub queues_info: Vec<QueuesInfo>,
     pub physical_device_features: vk::PhysicalDeviceFeatures,
 }
 
+/// Builds `PhysicalDeviceInfo` for custom selectors, validating requested queues.
+pub struct PhysicalDeviceInfoBuilder {
+    pdevice: PhysicalDevice,
+    queues_info: Vec<QueuesInfo>,
+    features: vk::PhysicalDeviceFeatures,
+}
+
+impl PhysicalDeviceInfoBuilder {
+    pub fn new(pdevice: PhysicalDevice) -> Self {
+        Self {
+            pdevice,
+            queues_info: Vec::new(),
+            features: Default::default(),
+        }
+    }
+
+    /// Requests `count` queues from `family_index` family.
+    pub fn add_queue_family(mut self, family_index: u32, count: u32) -> Self {
+        self.queues_info.push(QueuesInfo {
+            family_index,
+            count,
+        });
+        self
+    }
+
+    pub fn with_features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Checks that at least one queue is requested, each family is requested once,
+    /// and families exist and provide requested count of queues.
+    pub fn build(self, instance: &Instance) -> PhysicalDeviceResult {
+        let queue_props = unsafe {
+            instance
+                .handle()
+                .get_physical_device_queue_family_properties(self.pdevice)
+        };
+        check_queues_info(&self.queues_info, &queue_props)?;
+
+        Ok(PhysicalDeviceInfo {
+            pdevice: self.pdevice,
+            queues_info: self.queues_info,
+            physical_device_features: self.features,
+        })
+    }
+}
+
+/// Checks `queues_info` against queue families with `queue_props` properties.
+fn check_queues_info(
+    queues_info: &[QueuesInfo],
+    queue_props: &[vk::QueueFamilyProperties],
+) -> Result<(), PhysicalDeviceError> {
+    if queues_info.is_empty() {
+        return Err(PhysicalDeviceError::BadQueuesInfo(
+            "no queue families requested".into(),
+        ));
+    }
+
+    for (i, info) in queues_info.iter().enumerate() {
+        if queues_info[..i]
+            .iter()
+            .any(|prev| prev.family_index == info.family_index)
+        {
+            return Err(PhysicalDeviceError::BadQueuesInfo(format!(
+                "family {} is requested more than once",
+                info.family_index
+            )));
+        }
+
+        let available = match queue_props.get(info.family_index as usize) {
+            Some(props) => props.queue_count,
+            None => {
+                return Err(PhysicalDeviceError::BadQueuesInfo(format!(
+                    "family {} doesn't exist",
+                    info.family_index
+                )))
+            }
+        };
+        if info.count == 0 || info.count > available {
+            return Err(PhysicalDeviceError::BadQueuesInfo(format!(
+                "family {} provides {} queues, but {} requested",
+                info.family_index, available, info.count
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub enum PhysicalDeviceError {
     NotFound(String),
+    BadQueuesInfo(String),
     VkError(vk::Result),
 }
 
@@ -78,7 +378,8 @@ impl fmt::Display for PhysicalDeviceError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::NotFound(msg) => write!(f, "No suit device: {}", msg),
-            Self::VkError(e) => write!(f, "Vulkan error: {}", e),
+            Self::BadQueuesInfo(msg) => write!(f, "Bad queues info: {}", msg),
+            Self::VkError(e) => write!(f, "Vulkan error: {} ({})", e, crate::describe_result(*e)),
         }
     }
 }
@@ -88,3 +389,127 @@ impl From<vk::Result> for PhysicalDeviceError {
         Self::VkError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(flags: QueueFlags, queue_count: u32) -> vk::QueueFamilyProperties {
+        vk::QueueFamilyProperties {
+            queue_flags: flags,
+            queue_count,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clamp_limits_count_to_family_size() {
+        let props = [family(QueueFlags::COMPUTE, 2)];
+        assert_eq!(
+            family_with_queue_count(&props, QueueFlags::COMPUTE, 4, QueueCountPolicy::Clamp),
+            Some((0, 2))
+        );
+        assert_eq!(
+            family_with_queue_count(&props, QueueFlags::COMPUTE, 1, QueueCountPolicy::Clamp),
+            Some((0, 1))
+        );
+    }
+
+    #[test]
+    fn strict_skips_too_small_families() {
+        let props = [
+            family(QueueFlags::COMPUTE, 2),
+            family(QueueFlags::COMPUTE | QueueFlags::TRANSFER, 4),
+        ];
+        assert_eq!(
+            family_with_queue_count(&props, QueueFlags::COMPUTE, 4, QueueCountPolicy::Strict),
+            Some((1, 4))
+        );
+        assert_eq!(
+            family_with_queue_count(&props, QueueFlags::COMPUTE, 8, QueueCountPolicy::Strict),
+            None
+        );
+    }
+
+    #[test]
+    fn families_without_flags_are_skipped() {
+        let props = [
+            family(QueueFlags::TRANSFER, 8),
+            family(QueueFlags::GRAPHICS, 0),
+            family(QueueFlags::GRAPHICS | QueueFlags::COMPUTE, 1),
+        ];
+        for &policy in &[QueueCountPolicy::Clamp, QueueCountPolicy::Strict] {
+            assert_eq!(
+                family_with_queue_count(&props, QueueFlags::GRAPHICS, 1, policy),
+                Some((2, 1))
+            );
+            assert_eq!(
+                family_with_queue_count(&props, QueueFlags::SPARSE_BINDING, 1, policy),
+                None
+            );
+        }
+    }
+
+    fn queues(families: &[(u32, u32)]) -> Vec<QueuesInfo> {
+        families
+            .iter()
+            .map(|&(family_index, count)| QueuesInfo {
+                family_index,
+                count,
+            })
+            .collect()
+    }
+
+    fn bad_queues_info(result: Result<(), PhysicalDeviceError>) -> String {
+        match result {
+            Err(PhysicalDeviceError::BadQueuesInfo(msg)) => msg,
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn requested_queues_are_checked() {
+        let props = [
+            family(QueueFlags::GRAPHICS, 1),
+            family(QueueFlags::COMPUTE, 4),
+        ];
+
+        assert!(check_queues_info(&queues(&[(0, 1), (1, 4)]), &props).is_ok());
+        assert_eq!(
+            bad_queues_info(check_queues_info(&[], &props)),
+            "no queue families requested"
+        );
+        assert_eq!(
+            bad_queues_info(check_queues_info(&queues(&[(1, 1), (1, 2)]), &props)),
+            "family 1 is requested more than once"
+        );
+        assert_eq!(
+            bad_queues_info(check_queues_info(&queues(&[(2, 1)]), &props)),
+            "family 2 doesn't exist"
+        );
+        assert_eq!(
+            bad_queues_info(check_queues_info(&queues(&[(0, 2)]), &props)),
+            "family 0 provides 1 queues, but 2 requested"
+        );
+        assert_eq!(
+            bad_queues_info(check_queues_info(&queues(&[(1, 0)]), &props)),
+            "family 1 provides 4 queues, but 0 requested"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn device_is_created_with_built_info() {
+        let selector = |instance: &Instance| {
+            let (pdevice, family_index) =
+                first_device_with_family_flags(instance, QueueFlags::COMPUTE)?;
+            PhysicalDeviceInfoBuilder::new(pdevice)
+                .add_queue_family(family_index, 1)
+                .build(instance)
+        };
+        let (device, _queue, _pool) =
+            crate::test_utils::device_with(Box::new(selector), |builder| builder);
+        assert_eq!(device.queues_info().len(), 1);
+        assert_eq!(device.queues_info()[0].count, 1);
+    }
+}
@@ -0,0 +1,248 @@
+use crate::device::Device;
+use ash::prelude::VkResult;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::slice;
+use std::sync::Arc;
+
+#[derive(Default)]
+pub struct FenceBuilder {
+    flags: vk::FenceCreateFlags,
+}
+
+impl FenceBuilder {
+    pub fn with_flags(mut self, flags: vk::FenceCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn build(self, device: Device) -> CreateFenceResult<Fence> {
+        let create_info = vk::FenceCreateInfo {
+            flags: self.flags,
+            ..Default::default()
+        };
+
+        unsafe { Fence::new(device, &create_info) }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Fence {
+    unique_fence: Arc<UniqueFence>,
+}
+
+impl Fence {
+    /// # Safety
+    /// todo
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::FenceCreateInfo,
+    ) -> CreateFenceResult<Self> {
+        UniqueFence::new(device, create_info).map(|uf| Self {
+            unique_fence: Arc::new(uf),
+        })
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn handle(&self) -> &vk::Fence {
+        self.unique_fence.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_fence)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_fence, &other.unique_fence)
+    }
+
+    pub fn device(&self) -> &Device {
+        self.unique_fence.device()
+    }
+
+    /// Returns `false` if timeout expired before fence was signaled.
+    pub fn wait(&self, timeout_ns: u64) -> VkResult<bool> {
+        wait_for(self.device(), slice::from_ref(self), true, timeout_ns)
+    }
+
+    pub fn reset(&self) -> VkResult<()> {
+        reset_all(self.device(), slice::from_ref(self))
+    }
+
+    pub fn is_signaled(&self) -> VkResult<bool> {
+        let status = unsafe { self.device().handle().get_fence_status(*self.handle()) };
+        self.device().track_lost(status)
+    }
+}
+
+/// Waits for fences to become signaled.
+/// If `wait_all` is `true`, waits until all `fences` are signaled,
+/// else waits until at least one of them is signaled.
+/// Returns `false` if timeout expired before wait condition was satisfied.
+/// Returns `true` immediately if `fences` is empty.
+pub fn wait_for(
+    device: &Device,
+    fences: &[Fence],
+    wait_all: bool,
+    timeout_ns: u64,
+) -> VkResult<bool> {
+    if fences.is_empty() {
+        return Ok(true);
+    }
+
+    let raw_fences: Vec<vk::Fence> = fences.iter().map(|f| unsafe { *f.handle() }).collect();
+    let result = unsafe {
+        device
+            .handle()
+            .wait_for_fences(&raw_fences, wait_all, timeout_ns)
+    };
+
+    match device.track_lost(result) {
+        Ok(()) => Ok(true),
+        Err(vk::Result::TIMEOUT) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Sets all `fences` to unsignaled state.
+pub fn reset_all(device: &Device, fences: &[Fence]) -> VkResult<()> {
+    if fences.is_empty() {
+        return Ok(());
+    }
+
+    let raw_fences: Vec<vk::Fence> = fences.iter().map(|f| unsafe { *f.handle() }).collect();
+    let result = unsafe { device.handle().reset_fences(&raw_fences) };
+    device.track_lost(result)
+}
+
+struct UniqueFence {
+    handle: vk::Fence,
+    device: Device,
+}
+
+impl UniqueFence {
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::FenceCreateInfo,
+    ) -> CreateFenceResult<Self> {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Creating fence with flags: {:?}",
+            create_info.flags
+        );
+        let handle = device
+            .handle()
+            .create_fence(create_info, device.allocation_callbacks())?;
+        Ok(Self { handle, device })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::Fence {
+        &self.handle
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+}
+
+impl Drop for UniqueFence {
+    fn drop(&mut self) {
+        log::trace!(target: crate::LOG_TARGET, "Destroying fence");
+        unsafe {
+            self.device
+                .handle()
+                .destroy_fence(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueFence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fence")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Eq for UniqueFence {}
+
+impl PartialEq for UniqueFence {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueFence {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+pub type CreateFenceResult<T> = Result<T, CreateFenceError>;
+
+#[derive(Debug)]
+pub enum CreateFenceError {
+    VkError(vk::Result),
+    DeviceLost,
+}
+
+impl Error for CreateFenceError {}
+
+impl fmt::Display for CreateFenceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't create fence: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create fence: device lost"),
+        }
+    }
+}
+
+impl From<vk::Result> for CreateFenceError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device;
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn wait_all_differs_from_wait_any() {
+        let (device, _queue, _pool) = compute_device();
+        let signaled = FenceBuilder::default()
+            .with_flags(vk::FenceCreateFlags::SIGNALED)
+            .build(device.clone())
+            .unwrap();
+        let unsignaled = FenceBuilder::default().build(device.clone()).unwrap();
+        let fences = [signaled, unsignaled];
+
+        assert!(wait_for(&device, &fences, false, 0).unwrap());
+        assert!(!wait_for(&device, &fences, true, 0).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn empty_fence_list_is_noop() {
+        let (device, _queue, _pool) = compute_device();
+        assert!(wait_for(&device, &[], true, 0).unwrap());
+        reset_all(&device, &[]).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use ash::vk;
+
+/// Returns size in bytes of texel block of `format`: single texel for uncompressed formats
+/// and 4x4 block for block-compressed ones. Returns `None` for unknown formats and combined
+/// depth/stencil formats, which memory layout is implementation-dependent and which are
+/// copied per aspect.
+pub fn texel_block_size(format: vk::Format) -> Option<u32> {
+    let size = match format {
+        vk::Format::R8_UNORM
+        | vk::Format::R8_SNORM
+        | vk::Format::R8_UINT
+        | vk::Format::R8_SINT
+        | vk::Format::R8_SRGB
+        | vk::Format::S8_UINT => 1,
+
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_SNORM
+        | vk::Format::R16_UINT
+        | vk::Format::R16_SINT
+        | vk::Format::R16_SFLOAT
+        | vk::Format::R5G6B5_UNORM_PACK16
+        | vk::Format::D16_UNORM => 2,
+
+        vk::Format::R8G8B8_UNORM | vk::Format::R8G8B8_SRGB => 3,
+
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R8G8B8A8_UINT
+        | vk::Format::R8G8B8A8_SINT
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SINT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::X8_D24_UNORM_PACK32
+        | vk::Format::D32_SFLOAT => 4,
+
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SNORM
+        | vk::Format::R16G16B16A16_UINT
+        | vk::Format::R16G16B16A16_SINT
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32_SFLOAT => 8,
+
+        vk::Format::R32G32B32_UINT | vk::Format::R32G32B32_SINT | vk::Format::R32G32B32_SFLOAT => {
+            12
+        }
+
+        vk::Format::R32G32B32A32_UINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R32G32B32A32_SFLOAT => 16,
+
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK => 8,
+
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK => 16,
+
+        _ => return None,
+    };
+    Some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uncompressed_formats_have_texel_size() {
+        assert_eq!(texel_block_size(vk::Format::R8_UNORM), Some(1));
+        assert_eq!(texel_block_size(vk::Format::R8G8B8A8_SRGB), Some(4));
+        assert_eq!(texel_block_size(vk::Format::R16G16B16A16_SFLOAT), Some(8));
+        assert_eq!(texel_block_size(vk::Format::R32G32B32A32_SFLOAT), Some(16));
+        assert_eq!(texel_block_size(vk::Format::D32_SFLOAT), Some(4));
+    }
+
+    #[test]
+    fn compressed_formats_have_block_size() {
+        assert_eq!(texel_block_size(vk::Format::BC1_RGBA_UNORM_BLOCK), Some(8));
+        assert_eq!(texel_block_size(vk::Format::BC7_SRGB_BLOCK), Some(16));
+    }
+
+    #[test]
+    fn unknown_formats_have_no_size() {
+        assert_eq!(texel_block_size(vk::Format::UNDEFINED), None);
+        assert_eq!(texel_block_size(vk::Format::ASTC_4X4_UNORM_BLOCK), None);
+    }
+
+    #[test]
+    fn combined_depth_stencil_formats_have_no_size() {
+        assert_eq!(texel_block_size(vk::Format::D16_UNORM_S8_UINT), None);
+        assert_eq!(texel_block_size(vk::Format::D24_UNORM_S8_UINT), None);
+        assert_eq!(texel_block_size(vk::Format::D32_SFLOAT_S8_UINT), None);
+        assert_eq!(texel_block_size(vk::Format::S8_UINT), Some(1));
+    }
+}
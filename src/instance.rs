@@ -1,8 +1,12 @@
-use crate::{get_c_str_pointers, ContainRawVkName};
+use crate::{c_strings_from_pointers, get_c_str_pointers, ContainRawVkName};
+use ash::prelude::VkResult;
 use ash::version::{EntryV1_0, InstanceV1_0};
 use ash::vk::InstanceCreateInfo;
 use ash::{vk, InstanceError};
+use std::error::Error;
 use std::ffi::CString;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 pub struct InstanceBuilder {
@@ -10,6 +14,13 @@ pub struct InstanceBuilder {
     extensions: Vec<CString>,
     entry: ash::Entry,
     app_info: vk::ApplicationInfo,
+    validation_features: Option<ValidationFeatures>,
+    allocation_callbacks: Option<AllocationCallbacks>,
+}
+
+struct ValidationFeatures {
+    enabled: Vec<vk::ValidationFeatureEnableEXT>,
+    disabled: Vec<vk::ValidationFeatureDisableEXT>,
 }
 
 impl InstanceBuilder {
@@ -19,6 +30,8 @@ impl InstanceBuilder {
             app_info: Default::default(),
             layers: Vec::new(),
             extensions: Vec::new(),
+            validation_features: None,
+            allocation_callbacks: None,
         }
     }
 
@@ -27,29 +40,123 @@ impl InstanceBuilder {
         self
     }
 
+    /// Requests API version, but not higher than supported by Vulkan loader.
+    /// Requesting 1.0 is always safe.
+    pub fn with_api_version_clamped(self, major: u32, minor: u32, patch: u32) -> Self {
+        let max_supported = Self::max_supported_version(&self.entry);
+        let (major, minor, patch) = clamp_version((major, minor, patch), max_supported);
+        self.with_api_version(major, minor, patch)
+    }
+
+    /// Returns highest instance API version, supported by Vulkan loader.
+    /// Falls back to 1.0 if loader doesn't support version query or query fails.
+    pub fn max_supported_version(entry: &ash::Entry) -> (u32, u32, u32) {
+        version_or_1_0(entry.try_enumerate_instance_version())
+    }
+
     pub fn with_layers(mut self, layers: Vec<CString>) -> Self {
         self.layers = layers;
         self
     }
 
+    /// Adds `extensions` to extensions list, skipping already added ones.
     pub fn with_extensions(mut self, extensions: Vec<CString>) -> Self {
-        self.extensions = extensions;
+        add_unique(&mut self.extensions, extensions);
         self
     }
 
-    pub fn build(self) -> Result<Instance, InstanceError> {
-        let mut create_info = vk::InstanceCreateInfo::default();
-        create_info.p_application_info = &self.app_info;
+    /// Enables and disables validation layer features, e.g. GPU-assisted validation
+    /// or best practices checks.
+    /// `VK_EXT_validation_features` extension must be enabled.
+    pub fn with_validation_features(
+        mut self,
+        enabled: &[vk::ValidationFeatureEnableEXT],
+        disabled: &[vk::ValidationFeatureDisableEXT],
+    ) -> Self {
+        self.validation_features = Some(ValidationFeatures {
+            enabled: enabled.to_vec(),
+            disabled: disabled.to_vec(),
+        });
+        self
+    }
 
-        create_info.enabled_layer_count = self.layers.len() as u32;
-        let layers = get_c_str_pointers(&self.layers);
-        create_info.pp_enabled_layer_names = layers.as_ptr();
+    /// Host memory allocation `callbacks` are used for instance, its devices and all their objects.
+    pub fn with_allocation_callbacks(mut self, callbacks: AllocationCallbacks) -> Self {
+        self.allocation_callbacks = Some(callbacks);
+        self
+    }
+
+    /// Adds extensions, required to create surface for `window_handle`, to extensions list.
+    #[cfg(feature = "surface")]
+    pub fn with_surface_extensions(
+        mut self,
+        window_handle: &raw_window_handle::RawWindowHandle,
+    ) -> Self {
+        let extensions = crate::surface::required_extensions(window_handle);
+        add_unique(
+            &mut self.extensions,
+            extensions.into_iter().map(ToOwned::to_owned),
+        );
+        self
+    }
+
+    /// Checks that all requested layers and extensions are available.
+    /// Only extensions provided by Vulkan implementation or implicit layers are considered.
+    pub fn validate(&self) -> Result<(), InstanceBuildError> {
+        let available_layers: Vec<CString> = self
+            .entry
+            .enumerate_instance_layer_properties()?
+            .into_iter()
+            .map(|mut l| l.c_string_name())
+            .collect();
 
-        create_info.enabled_extension_count = self.extensions.len() as u32;
+        let available_extensions: Vec<CString> = self
+            .entry
+            .enumerate_instance_extension_properties()?
+            .into_iter()
+            .map(|mut e| e.c_string_name())
+            .collect();
+
+        check_available(
+            &self.layers,
+            &available_layers,
+            &self.extensions,
+            &available_extensions,
+        )
+    }
+
+    pub fn build(self) -> Result<Instance, InstanceError> {
+        let layers = get_c_str_pointers(&self.layers);
         let extensions = get_c_str_pointers(&self.extensions);
-        create_info.pp_enabled_extension_names = extensions.as_ptr();
 
-        unsafe { Instance::new(self.entry, &create_info) }
+        let validation_features =
+            self.validation_features
+                .as_ref()
+                .map(|features| vk::ValidationFeaturesEXT {
+                    enabled_validation_feature_count: features.enabled.len() as u32,
+                    p_enabled_validation_features: features.enabled.as_ptr(),
+                    disabled_validation_feature_count: features.disabled.len() as u32,
+                    p_disabled_validation_features: features.disabled.as_ptr(),
+                    ..Default::default()
+                });
+
+        let create_info = vk::InstanceCreateInfo {
+            p_next: validation_features
+                .as_ref()
+                .map_or(std::ptr::null(), |features| {
+                    (features as *const vk::ValidationFeaturesEXT).cast()
+                }),
+            p_application_info: &self.app_info,
+            enabled_layer_count: self.layers.len() as u32,
+            pp_enabled_layer_names: layers.as_ptr(),
+            enabled_extension_count: self.extensions.len() as u32,
+            pp_enabled_extension_names: extensions.as_ptr(),
+            ..Default::default()
+        };
+
+        unsafe {
+            Instance::with_allocation_callbacks(self.entry, &create_info, self.allocation_callbacks)
+        }
     }
 
     pub fn debug_layers(entry: ash::Entry) -> Vec<CString> {
@@ -65,7 +172,7 @@ impl InstanceBuilder {
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Eq, Hash, PartialEq)]
 pub struct Instance {
     unique_instance: Arc<UniqueInstance>,
 }
@@ -77,7 +184,17 @@ impl Instance {
         entry: ash::Entry,
         create_info: &InstanceCreateInfo,
     ) -> Result<Self, InstanceError> {
-        UniqueInstance::new(entry, &create_info).map(|inst| Self {
+        Self::with_allocation_callbacks(entry, create_info, None)
+    }
+
+    /// # Safety
+    /// todo
+    pub unsafe fn with_allocation_callbacks(
+        entry: ash::Entry,
+        create_info: &InstanceCreateInfo,
+        allocation_callbacks: Option<AllocationCallbacks>,
+    ) -> Result<Self, InstanceError> {
+        UniqueInstance::new(entry, create_info, allocation_callbacks).map(|inst| Self {
             unique_instance: Arc::new(inst),
         })
     }
@@ -85,27 +202,167 @@ impl Instance {
     /// # Safety
     /// TODO
     pub unsafe fn handle(&self) -> &ash::Instance {
-        &self.unique_instance.handle()
+        self.unique_instance.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_instance)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_instance, &other.unique_instance)
     }
 
     pub fn entry(&self) -> &ash::Entry {
-        &self.unique_instance.entry()
+        self.unique_instance.entry()
+    }
+
+    /// Host memory allocation callbacks, used for all objects of the instance.
+    pub fn allocation_callbacks(&self) -> Option<&vk::AllocationCallbacks> {
+        self.unique_instance.allocation_callbacks()
+    }
+
+    /// API version, instance was created with.
+    pub fn api_version(&self) -> (u32, u32, u32) {
+        split_version(self.unique_instance.api_version())
+    }
+
+    pub fn enabled_layers(&self) -> &[CString] {
+        self.unique_instance.layers()
+    }
+
+    pub fn enabled_extensions(&self) -> &[CString] {
+        self.unique_instance.extensions()
     }
 }
 
+/// Returns `Unavailable` error with requested layers and extensions, missing in available ones.
+fn check_available(
+    layers: &[CString],
+    available_layers: &[CString],
+    extensions: &[CString],
+    available_extensions: &[CString],
+) -> Result<(), InstanceBuildError> {
+    let missing = |requested: &[CString], available: &[CString]| -> Vec<CString> {
+        requested
+            .iter()
+            .filter(|name| !available.contains(name))
+            .cloned()
+            .collect()
+    };
+
+    let missing_layers = missing(layers, available_layers);
+    let missing_extensions = missing(extensions, available_extensions);
+    if missing_layers.is_empty() && missing_extensions.is_empty() {
+        return Ok(());
+    }
+
+    Err(InstanceBuildError::Unavailable {
+        layers: missing_layers,
+        extensions: missing_extensions,
+    })
+}
+
+/// Appends `names`, which aren't in `list` yet, keeping their order.
+fn add_unique(list: &mut Vec<CString>, names: impl IntoIterator<Item = CString>) {
+    for name in names {
+        if !list.contains(&name) {
+            list.push(name);
+        }
+    }
+}
+
+/// Splits queried instance version. Absent or failed query means Vulkan 1.0 loader.
+fn version_or_1_0(queried: VkResult<Option<u32>>) -> (u32, u32, u32) {
+    match queried {
+        Ok(Some(version)) => split_version(version),
+        _ => (1, 0, 0),
+    }
+}
+
+/// Returns `requested` version, but not higher than `max_supported`.
+fn clamp_version(requested: (u32, u32, u32), max_supported: (u32, u32, u32)) -> (u32, u32, u32) {
+    requested.min(max_supported)
+}
+
+fn split_version(version: u32) -> (u32, u32, u32) {
+    (
+        vk::version_major(version),
+        vk::version_minor(version),
+        vk::version_patch(version),
+    )
+}
+
+/// Host memory allocation callbacks, shared by instance, its devices and their objects.
+pub struct AllocationCallbacks {
+    callbacks: vk::AllocationCallbacks,
+}
+
+impl AllocationCallbacks {
+    /// # Safety
+    /// Callbacks must be valid and may be called from any thread.
+    /// `p_user_data` must stay valid until the instance and all its objects are destroyed.
+    pub unsafe fn new(callbacks: vk::AllocationCallbacks) -> Self {
+        Self { callbacks }
+    }
+
+    pub fn raw(&self) -> &vk::AllocationCallbacks {
+        &self.callbacks
+    }
+}
+
+// SAFETY: `AllocationCallbacks::new` requires callbacks and their user data to be thread safe.
+unsafe impl Send for AllocationCallbacks {}
+unsafe impl Sync for AllocationCallbacks {}
+
 struct UniqueInstance {
     handle: ash::Instance,
     entry: ash::Entry,
+    allocation_callbacks: Option<AllocationCallbacks>,
+    layers: Vec<CString>,
+    extensions: Vec<CString>,
+    api_version: u32,
 }
 
 impl UniqueInstance {
     pub unsafe fn new(
         entry: ash::Entry,
         create_info: &InstanceCreateInfo,
+        allocation_callbacks: Option<AllocationCallbacks>,
     ) -> Result<Self, InstanceError> {
-        log::trace!("Creating vulkan instance");
-        let handle = entry.create_instance(create_info, None)?;
-        Ok(Self { entry, handle })
+        log::trace!(target: crate::LOG_TARGET, "Creating vulkan instance");
+        let handle = entry.create_instance(
+            create_info,
+            allocation_callbacks.as_ref().map(AllocationCallbacks::raw),
+        )?;
+        let layers = c_strings_from_pointers(
+            create_info.pp_enabled_layer_names,
+            create_info.enabled_layer_count,
+        );
+        let extensions = c_strings_from_pointers(
+            create_info.pp_enabled_extension_names,
+            create_info.enabled_extension_count,
+        );
+
+        // Zero or absent application info means Vulkan 1.0.
+        let api_version = create_info
+            .p_application_info
+            .as_ref()
+            .map(|app_info| app_info.api_version)
+            .filter(|&version| version != 0)
+            .unwrap_or_else(|| vk::make_version(1, 0, 0));
+
+        Ok(Self {
+            entry,
+            handle,
+            allocation_callbacks,
+            layers,
+            extensions,
+            api_version,
+        })
     }
 
     pub unsafe fn handle(&self) -> &ash::Instance {
@@ -115,12 +372,30 @@ impl UniqueInstance {
     pub fn entry(&self) -> &ash::Entry {
         &self.entry
     }
+
+    pub fn layers(&self) -> &[CString] {
+        &self.layers
+    }
+
+    pub fn api_version(&self) -> u32 {
+        self.api_version
+    }
+
+    pub fn extensions(&self) -> &[CString] {
+        &self.extensions
+    }
+
+    pub fn allocation_callbacks(&self) -> Option<&vk::AllocationCallbacks> {
+        self.allocation_callbacks
+            .as_ref()
+            .map(AllocationCallbacks::raw)
+    }
 }
 
 impl Drop for UniqueInstance {
     fn drop(&mut self) {
-        log::trace!("Destroying vulkan instance");
-        unsafe { self.handle.destroy_instance(None) }
+        log::trace!(target: crate::LOG_TARGET, "Destroying vulkan instance");
+        unsafe { self.handle.destroy_instance(self.allocation_callbacks()) }
     }
 }
 
@@ -131,3 +406,272 @@ impl PartialEq for UniqueInstance {
         self.handle.handle() == other.handle.handle()
     }
 }
+
+impl Hash for UniqueInstance {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.handle().hash(state)
+    }
+}
+
+#[derive(Debug)]
+pub enum InstanceBuildError {
+    VkError(vk::Result),
+    Unavailable {
+        layers: Vec<CString>,
+        extensions: Vec<CString>,
+    },
+}
+
+impl Error for InstanceBuildError {}
+
+impl fmt::Display for InstanceBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't enumerate instance properties: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::Unavailable { layers, extensions } => write!(
+                f,
+                "Requested instance properties are not available; layers: {:?}; extensions: {:?}",
+                layers, extensions
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for InstanceBuildError {
+    fn from(e: vk::Result) -> Self {
+        Self::VkError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<CString> {
+        names.iter().map(|n| CString::new(*n).unwrap()).collect()
+    }
+
+    #[test]
+    fn missing_layers_and_extensions_are_unavailable() {
+        let layers = names(&["VK_LAYER_KHRONOS_validation"]);
+        let extensions = names(&["VK_KHR_surface", "VK_EXT_debug_utils"]);
+        assert!(check_available(&layers, &layers, &extensions, &extensions).is_ok());
+        assert!(check_available(&[], &[], &[], &extensions).is_ok());
+
+        match check_available(&layers, &[], &extensions, &extensions[..1]) {
+            Err(InstanceBuildError::Unavailable {
+                layers: missing_layers,
+                extensions: missing_extensions,
+            }) => {
+                assert_eq!(missing_layers, layers);
+                assert_eq!(missing_extensions, names(&["VK_EXT_debug_utils"]));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan loader"]
+    fn unknown_layer_is_unavailable() {
+        let entry = ash::Entry::new().expect("Vulkan loader must be available");
+        let builder = InstanceBuilder::new(entry).with_layers(names(&["VK_LAYER_LLW_unknown"]));
+        match builder.validate() {
+            Err(InstanceBuildError::Unavailable { layers, extensions }) => {
+                assert_eq!(layers, names(&["VK_LAYER_LLW_unknown"]));
+                assert!(extensions.is_empty());
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn added_extensions_are_merged() {
+        let name = |n: &str| CString::new(n).unwrap();
+        let mut extensions = vec![name("VK_KHR_surface")];
+        add_unique(
+            &mut extensions,
+            vec![name("VK_EXT_debug_utils"), name("VK_KHR_surface")],
+        );
+        assert_eq!(
+            extensions,
+            vec![name("VK_KHR_surface"), name("VK_EXT_debug_utils")]
+        );
+    }
+
+    #[test]
+    fn failed_version_query_means_1_0() {
+        assert_eq!(
+            version_or_1_0(Ok(Some(vk::make_version(1, 2, 170)))),
+            (1, 2, 170)
+        );
+        assert_eq!(version_or_1_0(Ok(None)), (1, 0, 0));
+        assert_eq!(
+            version_or_1_0(Err(vk::Result::ERROR_OUT_OF_HOST_MEMORY)),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan loader"]
+    fn instance_is_created_with_supported_version() {
+        let entry = ash::Entry::new().expect("Vulkan loader must be available");
+        let max_supported = InstanceBuilder::max_supported_version(&entry);
+        assert!(max_supported >= (1, 0, 0));
+
+        let instance = InstanceBuilder::new(entry)
+            .with_api_version_clamped(1, 2, 0)
+            .build()
+            .unwrap();
+        assert_eq!(
+            instance.api_version(),
+            clamp_version((1, 2, 0), max_supported)
+        );
+    }
+
+    #[test]
+    fn version_is_clamped_to_supported() {
+        assert_eq!(clamp_version((1, 2, 0), (1, 1, 121)), (1, 1, 121));
+        assert_eq!(clamp_version((1, 1, 0), (1, 2, 0)), (1, 1, 0));
+        assert_eq!(clamp_version((1, 2, 3), (1, 2, 1)), (1, 2, 1));
+        assert_eq!(clamp_version((1, 0, 0), (1, 0, 0)), (1, 0, 0));
+        assert_eq!(clamp_version((2, 0, 0), (1, 3, 0)), (1, 3, 0));
+    }
+
+    /// Allocation callbacks, counting allocations in `AtomicUsize` user data.
+    /// Size and alignment of allocation are kept in header just before returned pointer.
+    mod counting {
+        use ash::vk;
+        use std::alloc::{self, Layout};
+        use std::ffi::c_void;
+        use std::mem::size_of;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const HEADER_SIZE: usize = 2 * size_of::<usize>();
+
+        pub fn callbacks(counter: &AtomicUsize) -> vk::AllocationCallbacks {
+            vk::AllocationCallbacks {
+                p_user_data: counter as *const AtomicUsize as *mut c_void,
+                pfn_allocation: Some(allocate),
+                pfn_reallocation: Some(reallocate),
+                pfn_free: Some(free),
+                pfn_internal_allocation: None,
+                pfn_internal_free: None,
+            }
+        }
+
+        unsafe extern "system" fn allocate(
+            user_data: *mut c_void,
+            size: usize,
+            alignment: usize,
+            _scope: vk::SystemAllocationScope,
+        ) -> *mut c_void {
+            (*user_data.cast::<AtomicUsize>()).fetch_add(1, Ordering::SeqCst);
+            let offset = alignment.max(HEADER_SIZE);
+            let layout = match Layout::from_size_align(size + offset, alignment.max(HEADER_SIZE)) {
+                Ok(layout) => layout,
+                Err(_) => return std::ptr::null_mut(),
+            };
+            let base = alloc::alloc(layout);
+            if base.is_null() {
+                return std::ptr::null_mut();
+            }
+            let memory = base.add(offset);
+            let header = memory.cast::<usize>().sub(2);
+            header.write(size);
+            header.add(1).write(layout.align());
+            memory.cast()
+        }
+
+        unsafe extern "system" fn reallocate(
+            user_data: *mut c_void,
+            original: *mut c_void,
+            size: usize,
+            alignment: usize,
+            scope: vk::SystemAllocationScope,
+        ) -> *mut c_void {
+            if original.is_null() {
+                return allocate(user_data, size, alignment, scope);
+            }
+            if size == 0 {
+                free(user_data, original);
+                return std::ptr::null_mut();
+            }
+
+            let memory = allocate(user_data, size, alignment, scope);
+            if !memory.is_null() {
+                let original_size = *original.cast::<usize>().sub(2);
+                std::ptr::copy_nonoverlapping(
+                    original.cast::<u8>(),
+                    memory.cast::<u8>(),
+                    original_size.min(size),
+                );
+                free(user_data, original);
+            }
+            memory
+        }
+
+        unsafe extern "system" fn free(_user_data: *mut c_void, memory: *mut c_void) -> c_void {
+            if !memory.is_null() {
+                let header = memory.cast::<usize>().sub(2);
+                let (size, align) = (header.read(), header.add(1).read());
+                let layout = Layout::from_size_align_unchecked(size + align, align);
+                alloc::dealloc(memory.cast::<u8>().sub(align), layout);
+            }
+            // `PFN_vkFreeFunction` of ash 0.31 returns `c_void`, which has no public constructor.
+            std::mem::zeroed()
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan loader"]
+    fn allocations_go_through_callbacks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let counter = AtomicUsize::new(0);
+        let entry = ash::Entry::new().expect("Vulkan loader must be available");
+        let callbacks = unsafe { AllocationCallbacks::new(counting::callbacks(&counter)) };
+        let instance = InstanceBuilder::new(entry)
+            .with_allocation_callbacks(callbacks)
+            .build()
+            .unwrap();
+
+        assert!(instance.allocation_callbacks().is_some());
+        assert!(counter.load(Ordering::SeqCst) > 0);
+    }
+
+    #[cfg(all(feature = "surface", target_os = "linux"))]
+    #[test]
+    fn surface_extensions_are_merged_in_any_order() {
+        use raw_window_handle::unix::XlibHandle;
+        use raw_window_handle::RawWindowHandle;
+
+        let handle = RawWindowHandle::Xlib(XlibHandle::empty());
+        let surface: Vec<CString> = crate::surface::required_extensions(&handle)
+            .into_iter()
+            .map(ToOwned::to_owned)
+            .collect();
+        let user = vec![
+            CString::new("VK_EXT_debug_utils").unwrap(),
+            CString::new("VK_KHR_surface").unwrap(),
+        ];
+
+        let mut surface_first = Vec::new();
+        add_unique(&mut surface_first, surface.clone());
+        add_unique(&mut surface_first, user.clone());
+
+        let mut surface_last = Vec::new();
+        add_unique(&mut surface_last, user);
+        add_unique(&mut surface_last, surface);
+
+        assert_eq!(surface_first.len(), 3);
+        assert!(surface_first.contains(&CString::new("VK_KHR_xlib_surface").unwrap()));
+        surface_first.sort();
+        surface_last.sort();
+        assert_eq!(surface_first, surface_last);
+    }
+}
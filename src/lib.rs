@@ -1,16 +1,60 @@
 use ash::vk;
-use std::ffi::{CStr, CString};
+use std::ffi::{c_void, CStr, CString};
+use std::ptr;
+
+/// Target of all log records, emitted by the crate.
+pub const LOG_TARGET: &str = "vk_llw";
 
 pub mod buffer;
 pub mod command_buffer;
 pub mod command_pool;
 pub mod debug_report;
 pub mod desc_set_layout;
+pub mod desc_writes;
+pub mod descriptor_update_template;
 pub mod device;
+pub mod fence;
+pub mod format;
 pub mod instance;
 pub mod memory;
+pub mod named;
+pub mod pipeline;
 pub mod queue;
 pub mod sampler;
+pub mod semaphore;
+pub mod shader_module;
+pub mod subresource_range;
+#[cfg(feature = "surface")]
+pub mod surface;
+#[cfg(feature = "surface")]
+pub mod swapchain;
+pub mod sync;
+#[cfg(test)]
+mod test_utils;
+
+/// Compile time check, that wrappers can be shared between threads.
+#[allow(dead_code)]
+fn assert_send_sync() {
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<instance::Instance>();
+    is_send_sync::<debug_report::DebugReport>();
+    is_send_sync::<device::Device>();
+    is_send_sync::<queue::Queue>();
+    is_send_sync::<memory::Memory>();
+    is_send_sync::<buffer::Buffer>();
+    is_send_sync::<sampler::Sampler>();
+    is_send_sync::<shader_module::ShaderModule>();
+    is_send_sync::<command_pool::CommandPool>();
+    is_send_sync::<command_buffer::CommandBuffers>();
+    is_send_sync::<desc_set_layout::DescriptorSetLayout>();
+    is_send_sync::<descriptor_update_template::DescriptorUpdateTemplate>();
+    is_send_sync::<fence::Fence>();
+    is_send_sync::<semaphore::Semaphore>();
+    #[cfg(feature = "surface")]
+    is_send_sync::<surface::Surface>();
+    #[cfg(feature = "surface")]
+    is_send_sync::<swapchain::Swapchain>();
+}
 
 fn get_c_str_pointers(strs: &[CString]) -> Vec<*const i8> {
     let mut ptrs = Vec::with_capacity(strs.len());
@@ -20,6 +64,89 @@ fn get_c_str_pointers(strs: &[CString]) -> Vec<*const i8> {
     ptrs
 }
 
+/// # Safety
+/// `ptrs` must point to `count` valid null-terminated strings.
+unsafe fn c_strings_from_pointers(ptrs: *const *const i8, count: u32) -> Vec<CString> {
+    if count == 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(ptrs, count as usize)
+        .iter()
+        .map(|&ptr| CStr::from_ptr(ptr).to_owned())
+        .collect()
+}
+
+/// Links `structs` to `p_next` chain in their order and returns its first item,
+/// or null if `structs` are empty. `p_next` of every item is overwritten.
+///
+/// # Safety
+/// Every item must be Vulkan structure, starting with `s_type` and `p_next` fields,
+/// i.e. implement one of ash `Extends*` traits. Chain must not outlive `structs`.
+unsafe fn link_p_next<T: ?Sized>(structs: &mut [Box<T>]) -> *mut c_void {
+    let mut next: *mut vk::BaseOutStructure = ptr::null_mut();
+    for item in structs.iter_mut().rev() {
+        let base = item.as_mut() as *mut T as *mut vk::BaseOutStructure;
+        (*base).p_next = next;
+        next = base;
+    }
+    next.cast()
+}
+
+/// Returns human-readable explanation of `result` with a hint how to fix it.
+pub fn describe_result(result: vk::Result) -> &'static str {
+    match result {
+        vk::Result::SUCCESS => "Command successfully completed",
+        vk::Result::NOT_READY => "A fence or query has not yet completed",
+        vk::Result::TIMEOUT => "A wait operation has not completed in the specified time",
+        vk::Result::INCOMPLETE => "A return array was too small for the result",
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY => {
+            "The host ran out of memory; free host resources or reduce allocations"
+        }
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY => {
+            "The device ran out of GPU memory; reduce allocation size or free resources"
+        }
+        vk::Result::ERROR_INITIALIZATION_FAILED => {
+            "Initialization failed; check that Vulkan driver is installed properly"
+        }
+        vk::Result::ERROR_DEVICE_LOST => {
+            "The device was lost; it and all its objects must be recreated"
+        }
+        vk::Result::ERROR_MEMORY_MAP_FAILED => {
+            "Memory mapping failed; check that memory is host visible and not mapped already"
+        }
+        vk::Result::ERROR_LAYER_NOT_PRESENT => {
+            "Requested layer is not present; install it or remove it from the layer list"
+        }
+        vk::Result::ERROR_EXTENSION_NOT_PRESENT => {
+            "Requested extension is not supported; check availability before enabling it"
+        }
+        vk::Result::ERROR_FEATURE_NOT_PRESENT => {
+            "Requested feature is not supported by the physical device"
+        }
+        vk::Result::ERROR_INCOMPATIBLE_DRIVER => {
+            "Requested API version is not supported by the driver; request lower version"
+        }
+        vk::Result::ERROR_TOO_MANY_OBJECTS => "Too many objects of this type are already created",
+        vk::Result::ERROR_FORMAT_NOT_SUPPORTED => {
+            "Requested format is not supported by the physical device"
+        }
+        vk::Result::ERROR_FRAGMENTED_POOL => {
+            "Pool allocation failed due to fragmentation; reset the pool or create a new one"
+        }
+        vk::Result::ERROR_OUT_OF_POOL_MEMORY => {
+            "Pool ran out of memory; create the pool with larger capacity"
+        }
+        vk::Result::ERROR_SURFACE_LOST_KHR => "The surface is no longer available; recreate it",
+        vk::Result::ERROR_OUT_OF_DATE_KHR => {
+            "The surface has changed and the swapchain must be recreated"
+        }
+        vk::Result::SUBOPTIMAL_KHR => {
+            "The swapchain no longer matches the surface exactly; consider recreating it"
+        }
+        _ => "Unexpected Vulkan result",
+    }
+}
+
 pub fn raw_name_to_c_string(raw: &mut [i8]) -> CString {
     if raw.is_empty() {
         return CString::new("").unwrap();
@@ -49,3 +176,107 @@ impl ContainRawVkName for vk::ExtensionProperties {
         self.extension_name.as_mut()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_device_lost_conversion {
+        ($($($error:ident)::+),* $(,)?) => {
+            $(
+                match $($error)::+::from(vk::Result::ERROR_DEVICE_LOST) {
+                    $($error)::+::DeviceLost => {}
+                    e => panic!("{:?} isn't DeviceLost", e),
+                }
+                match $($error)::+::from(vk::Result::ERROR_OUT_OF_HOST_MEMORY) {
+                    $($error)::+::VkError(vk::Result::ERROR_OUT_OF_HOST_MEMORY) => {}
+                    e => panic!("{:?} isn't VkError", e),
+                }
+            )*
+        };
+    }
+
+    #[test]
+    fn p_next_chain_keeps_order() {
+        let mut structs: Vec<Box<dyn vk::ExtendsBufferCreateInfo>> = vec![
+            Box::new(vk::ExternalMemoryBufferCreateInfo::default()),
+            Box::new(vk::BufferOpaqueCaptureAddressCreateInfo::default()),
+        ];
+        let first = unsafe { link_p_next(&mut structs) } as *const vk::BaseInStructure;
+
+        let second = unsafe { (*first).p_next };
+        assert_eq!(
+            unsafe { (*first).s_type },
+            vk::StructureType::EXTERNAL_MEMORY_BUFFER_CREATE_INFO
+        );
+        assert_eq!(
+            unsafe { (*second).s_type },
+            vk::StructureType::BUFFER_OPAQUE_CAPTURE_ADDRESS_CREATE_INFO
+        );
+        assert!(unsafe { (*second).p_next.is_null() });
+
+        let mut empty: Vec<Box<dyn vk::ExtendsBufferCreateInfo>> = Vec::new();
+        assert!(unsafe { link_p_next(&mut empty) }.is_null());
+    }
+
+    #[test]
+    fn known_results_are_described() {
+        assert_eq!(
+            describe_result(vk::Result::ERROR_OUT_OF_DEVICE_MEMORY),
+            "The device ran out of GPU memory; reduce allocation size or free resources"
+        );
+        assert_eq!(
+            describe_result(vk::Result::ERROR_DEVICE_LOST),
+            "The device was lost; it and all its objects must be recreated"
+        );
+    }
+
+    #[test]
+    fn unknown_result_has_fallback_description() {
+        assert_eq!(
+            describe_result(vk::Result::from_raw(-123_456)),
+            "Unexpected Vulkan result"
+        );
+    }
+
+    #[test]
+    fn error_display_keeps_code_and_guidance() {
+        let message =
+            fence::CreateFenceError::VkError(vk::Result::ERROR_OUT_OF_HOST_MEMORY).to_string();
+        assert!(message.contains(&vk::Result::ERROR_OUT_OF_HOST_MEMORY.to_string()));
+        assert!(message.contains(describe_result(vk::Result::ERROR_OUT_OF_HOST_MEMORY)));
+    }
+
+    #[test]
+    fn device_lost_is_distinct_error() {
+        assert_device_lost_conversion!(
+            buffer::CreateBufferError,
+            buffer::TransferError,
+            command_buffer::AllocateCommandBuffersError,
+            command_buffer::OneTimeSubmitError,
+            command_pool::CreateCommandPoolError,
+            desc_set_layout::CreateDescriptorSetLayoutError,
+            descriptor_update_template::CreateDescriptorUpdateTemplateError,
+            device::CreateDeviceError,
+            fence::CreateFenceError,
+            memory::MemAllocError,
+            queue::SubmitError,
+            sampler::CreateSamplerError,
+            semaphore::CreateSemaphoreError,
+            semaphore::TimelineError,
+            shader_module::ShaderModuleError,
+        );
+    }
+
+    #[cfg(feature = "surface")]
+    #[test]
+    fn swapchain_device_lost_is_distinct_error() {
+        assert_device_lost_conversion!(swapchain::SwapchainError);
+    }
+
+    #[cfg(feature = "external_memory")]
+    #[test]
+    fn memory_export_device_lost_is_distinct_error() {
+        assert_device_lost_conversion!(memory::ExportMemoryError);
+    }
+}
@@ -1,22 +1,72 @@
 use crate::device::Device;
+#[cfg(feature = "external_memory")]
+use ash::extensions::khr::ExternalMemoryFd;
+use ash::prelude::VkResult;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use std::error::Error;
+use std::ffi::c_void;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
 pub struct MemoryBuilder {
     size: u64,
     type_index: u32,
+    allocate_flags: Option<vk::MemoryAllocateFlags>,
+    #[cfg(feature = "external_memory")]
+    export_handle_types: Option<vk::ExternalMemoryHandleTypeFlags>,
+    #[cfg(feature = "external_memory")]
+    import_fd: Option<(i32, vk::ExternalMemoryHandleTypeFlags)>,
 }
 
 impl MemoryBuilder {
     pub fn new(size: u64, type_index: u32) -> Self {
-        Self { size, type_index }
+        Self {
+            size,
+            type_index,
+            allocate_flags: None,
+            #[cfg(feature = "external_memory")]
+            export_handle_types: None,
+            #[cfg(feature = "external_memory")]
+            import_fd: None,
+        }
+    }
+
+    /// Chains `vk::MemoryAllocateFlagsInfo` with `flags`, e.g. `DEVICE_ADDRESS`.
+    pub fn with_allocate_flags(mut self, flags: vk::MemoryAllocateFlags) -> Self {
+        self.allocate_flags = Some(flags);
+        self
+    }
+
+    /// Makes memory exportable as `handle_type`.
+    /// Device must be created with `VK_KHR_external_memory` extension enabled.
+    #[cfg(feature = "external_memory")]
+    pub fn with_export(mut self, handle_type: vk::ExternalMemoryHandleTypeFlags) -> Self {
+        self.export_handle_types = Some(handle_type);
+        self
+    }
+
+    /// Imports memory from POSIX file descriptor `fd` instead of allocating new one.
+    /// Device must be created with `VK_KHR_external_memory_fd` extension enabled.
+    ///
+    /// # Safety
+    /// `fd` must be valid handle of `handle_type`, exported with compatible size and memory type.
+    /// On successful build Vulkan takes ownership of `fd`.
+    #[cfg(feature = "external_memory")]
+    pub unsafe fn with_import_fd(
+        mut self,
+        fd: i32,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Self {
+        self.import_fd = Some((fd, handle_type));
+        self
     }
 
     pub fn build(self, device: Device) -> MemAllocResult<Memory> {
+        let mut next = self.allocate_next()?;
         let alloc_info = vk::MemoryAllocateInfo {
+            p_next: unsafe { crate::link_p_next(&mut next) },
             allocation_size: self.size,
             memory_type_index: self.type_index,
             ..Default::default()
@@ -24,9 +74,42 @@ impl MemoryBuilder {
 
         unsafe { Memory::new(device, &alloc_info) }
     }
+
+    /// Structures to chain to `vk::MemoryAllocateInfo`.
+    fn allocate_next(&self) -> MemAllocResult<Vec<Box<dyn vk::ExtendsMemoryAllocateInfo>>> {
+        let mut next: Vec<Box<dyn vk::ExtendsMemoryAllocateInfo>> = Vec::new();
+        if let Some(flags) = self.allocate_flags {
+            next.push(Box::new(vk::MemoryAllocateFlagsInfo {
+                flags,
+                ..Default::default()
+            }));
+        }
+
+        #[cfg(feature = "external_memory")]
+        {
+            if self.export_handle_types.is_some() && self.import_fd.is_some() {
+                return Err(MemAllocError::ExportWithImport);
+            }
+            if let Some(handle_types) = self.export_handle_types {
+                next.push(Box::new(vk::ExportMemoryAllocateInfo {
+                    handle_types,
+                    ..Default::default()
+                }));
+            }
+            if let Some((fd, handle_type)) = self.import_fd {
+                next.push(Box::new(vk::ImportMemoryFdInfoKHR {
+                    handle_type,
+                    fd,
+                    ..Default::default()
+                }));
+            }
+        }
+
+        Ok(next)
+    }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Memory {
     unique_memory: Arc<UniqueMemory>,
 }
@@ -46,11 +129,74 @@ impl Memory {
     /// # Safety
     /// TODO
     pub unsafe fn handle(&self) -> &vk::DeviceMemory {
-        &self.unique_memory.handle()
+        self.unique_memory.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_memory)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_memory, &other.unique_memory)
     }
 
     pub fn device(&self) -> &Device {
-        &self.unique_memory.device()
+        self.unique_memory.device()
+    }
+
+    /// # Safety
+    /// Memory must be host visible and not mapped already.
+    /// Returned pointer is valid until `unmap` call.
+    pub unsafe fn map(&self, offset: u64, size: u64) -> VkResult<*mut c_void> {
+        self.device()
+            .handle()
+            .map_memory(*self.handle(), offset, size, vk::MemoryMapFlags::empty())
+    }
+
+    /// # Safety
+    /// Memory must be mapped.
+    pub unsafe fn unmap(&self) {
+        self.device().handle().unmap_memory(*self.handle())
+    }
+
+    /// Handle types, memory can be exported as. Empty if memory isn't built `with_export`.
+    #[cfg(feature = "external_memory")]
+    pub fn export_handle_types(&self) -> vk::ExternalMemoryHandleTypeFlags {
+        self.unique_memory.export_handle_types()
+    }
+
+    /// Exports memory as POSIX file descriptor. Caller owns returned descriptor.
+    /// `handle_type` must be single handle type, memory was built `with_export`, and device
+    /// must be created with `VK_KHR_external_memory_fd` extension enabled.
+    #[cfg(feature = "external_memory")]
+    pub fn export_fd(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> ExportMemoryResult<i32> {
+        if !self.device().is_extension_enabled(ExternalMemoryFd::name()) {
+            return Err(ExportMemoryError::ExtensionNotEnabled);
+        }
+        if handle_type.as_raw().count_ones() != 1
+            || !self.export_handle_types().contains(handle_type)
+        {
+            return Err(ExportMemoryError::NotExportable(handle_type));
+        }
+
+        let get_fd_info = vk::MemoryGetFdInfoKHR {
+            memory: unsafe { *self.handle() },
+            handle_type,
+            ..Default::default()
+        };
+
+        let result = unsafe {
+            let loader =
+                ExternalMemoryFd::new(self.device().instance().handle(), self.device().handle());
+            loader.get_memory_fd(&get_fd_info)
+        };
+        Ok(self.device().track_lost(result)?)
     }
 }
 
@@ -58,6 +204,22 @@ impl Memory {
 struct UniqueMemory {
     device: Device,
     handle: vk::DeviceMemory,
+    #[cfg(feature = "external_memory")]
+    export_handle_types: vk::ExternalMemoryHandleTypeFlags,
+}
+
+impl fmt::Debug for UniqueMemory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Memory")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Hash for UniqueMemory {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
 }
 
 impl UniqueMemory {
@@ -68,12 +230,20 @@ impl UniqueMemory {
         allocate_info: &vk::MemoryAllocateInfo,
     ) -> MemAllocResult<Self> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Allocating vk device memory; size: {}; type_index: {}",
             allocate_info.allocation_size,
             allocate_info.memory_type_index
         );
-        let handle = device.handle().allocate_memory(allocate_info, None)?;
-        Ok(Self { handle, device })
+        let handle = device
+            .handle()
+            .allocate_memory(allocate_info, device.allocation_callbacks())?;
+        Ok(Self {
+            handle,
+            device,
+            #[cfg(feature = "external_memory")]
+            export_handle_types: export_handle_types(allocate_info),
+        })
     }
 
     pub unsafe fn handle(&self) -> &vk::DeviceMemory {
@@ -83,12 +253,39 @@ impl UniqueMemory {
     pub fn device(&self) -> &Device {
         &self.device
     }
+
+    #[cfg(feature = "external_memory")]
+    pub fn export_handle_types(&self) -> vk::ExternalMemoryHandleTypeFlags {
+        self.export_handle_types
+    }
+}
+
+/// Finds exportable handle types in `p_next` chain of `allocate_info`.
+///
+/// # Safety
+/// `p_next` chain of `allocate_info` must consist of valid Vulkan structures.
+#[cfg(feature = "external_memory")]
+unsafe fn export_handle_types(
+    allocate_info: &vk::MemoryAllocateInfo,
+) -> vk::ExternalMemoryHandleTypeFlags {
+    let mut next = allocate_info.p_next as *const vk::BaseInStructure;
+    while !next.is_null() {
+        if (*next).s_type == vk::StructureType::EXPORT_MEMORY_ALLOCATE_INFO {
+            return (*next.cast::<vk::ExportMemoryAllocateInfo>()).handle_types;
+        }
+        next = (*next).p_next;
+    }
+    vk::ExternalMemoryHandleTypeFlags::empty()
 }
 
 impl Drop for UniqueMemory {
     fn drop(&mut self) {
-        log::trace!("Freeing vk device memory");
-        unsafe { self.device.handle().free_memory(self.handle, None) }
+        log::trace!(target: crate::LOG_TARGET, "Freeing vk device memory");
+        unsafe {
+            self.device
+                .handle()
+                .free_memory(self.handle, self.device.allocation_callbacks())
+        }
     }
 }
 
@@ -97,6 +294,10 @@ pub type MemAllocResult<T> = Result<T, MemAllocError>;
 #[derive(Debug)]
 pub enum MemAllocError {
     VkError(vk::Result),
+    DeviceLost,
+    /// Memory can't be exported and imported at the same time.
+    #[cfg(feature = "external_memory")]
+    ExportWithImport,
 }
 
 impl Error for MemAllocError {}
@@ -104,13 +305,212 @@ impl Error for MemAllocError {}
 impl fmt::Display for MemAllocError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            MemAllocError::VkError(e) => write!(f, "Vulkan memory allocation failed: {}", e),
+            MemAllocError::VkError(e) => write!(
+                f,
+                "Vulkan memory allocation failed: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            MemAllocError::DeviceLost => write!(f, "Vulkan memory allocation failed: device lost"),
+            #[cfg(feature = "external_memory")]
+            MemAllocError::ExportWithImport => write!(
+                f,
+                "Vulkan memory allocation failed: memory can't be both exported and imported"
+            ),
         }
     }
 }
 
 impl From<vk::Result> for MemAllocError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(feature = "external_memory")]
+pub type ExportMemoryResult<T> = Result<T, ExportMemoryError>;
+
+#[cfg(feature = "external_memory")]
+#[derive(Debug)]
+pub enum ExportMemoryError {
+    VkError(vk::Result),
+    DeviceLost,
+    ExtensionNotEnabled,
+    NotExportable(vk::ExternalMemoryHandleTypeFlags),
+}
+
+#[cfg(feature = "external_memory")]
+impl Error for ExportMemoryError {}
+
+#[cfg(feature = "external_memory")]
+impl fmt::Display for ExportMemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't export memory: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't export memory: device lost"),
+            Self::ExtensionNotEnabled => write!(
+                f,
+                "Can't export memory: VK_KHR_external_memory_fd is not enabled"
+            ),
+            Self::NotExportable(handle_type) => write!(
+                f,
+                "Can't export memory as {:?}: memory wasn't built with export of this handle type",
+                handle_type
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "external_memory")]
+impl From<vk::Result> for ExportMemoryError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device_with;
+
+    #[test]
+    fn allocate_flags_are_chained() {
+        let flags = vk::MemoryAllocateFlags::DEVICE_ADDRESS;
+        let mut next = MemoryBuilder::new(64, 0)
+            .with_allocate_flags(flags)
+            .allocate_next()
+            .unwrap();
+        assert_eq!(next.len(), 1);
+
+        let allocate_info = vk::MemoryAllocateInfo {
+            p_next: unsafe { crate::link_p_next(&mut next) },
+            ..Default::default()
+        };
+        let flags_info = allocate_info.p_next as *const vk::MemoryAllocateFlagsInfo;
+        unsafe {
+            assert_eq!(
+                (*flags_info).s_type,
+                vk::StructureType::MEMORY_ALLOCATE_FLAGS_INFO
+            );
+            assert_eq!((*flags_info).flags, flags);
+        }
+
+        assert!(MemoryBuilder::new(64, 0)
+            .allocate_next()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device with bufferDeviceAddress feature"]
+    fn memory_is_allocated_with_device_address_flag() {
+        let (device, _queue, _pool) = compute_device_with(|builder| {
+            builder.with_feature_struct(vk::PhysicalDeviceVulkan12Features {
+                buffer_device_address: vk::TRUE,
+                ..Default::default()
+            })
+        });
+        let type_index = device
+            .find_memory_type_index(!0, vk::MemoryPropertyFlags::empty())
+            .unwrap();
+
+        let memory = MemoryBuilder::new(1024, type_index)
+            .with_allocate_flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS)
+            .build(device.clone())
+            .unwrap();
+        assert!(memory.device().same_instance(&device));
+    }
+
+    #[cfg(feature = "external_memory")]
+    const OPAQUE_FD: vk::ExternalMemoryHandleTypeFlags =
+        vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD;
+
+    #[cfg(feature = "external_memory")]
+    #[test]
+    fn export_handle_types_are_found_in_chain() {
+        let mut next: Vec<Box<dyn vk::ExtendsMemoryAllocateInfo>> = vec![
+            Box::new(vk::MemoryAllocateFlagsInfo::default()),
+            Box::new(vk::ExportMemoryAllocateInfo {
+                handle_types: OPAQUE_FD,
+                ..Default::default()
+            }),
+        ];
+        let allocate_info = vk::MemoryAllocateInfo {
+            p_next: unsafe { crate::link_p_next(&mut next) },
+            ..Default::default()
+        };
+        assert_eq!(unsafe { export_handle_types(&allocate_info) }, OPAQUE_FD);
+        assert!(unsafe { export_handle_types(&vk::MemoryAllocateInfo::default()) }.is_empty());
+    }
+
+    #[cfg(all(feature = "external_memory", unix))]
+    #[test]
+    #[ignore = "requires Vulkan device with VK_KHR_external_memory_fd"]
+    fn exported_memory_gives_fd() {
+        use std::os::unix::io::FromRawFd;
+
+        let (device, _queue, _pool) = compute_device_with(|builder| {
+            builder.with_extensions(vec![
+                vk::KhrExternalMemoryFn::name().to_owned(),
+                ExternalMemoryFd::name().to_owned(),
+            ])
+        });
+        let type_index = device
+            .find_memory_type_index(!0, vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .unwrap();
+        let memory = MemoryBuilder::new(1024, type_index)
+            .with_export(OPAQUE_FD)
+            .build(device)
+            .unwrap();
+
+        assert_eq!(memory.export_handle_types(), OPAQUE_FD);
+        let fd = memory.export_fd(OPAQUE_FD).unwrap();
+        assert!(fd >= 0);
+        drop(unsafe { std::fs::File::from_raw_fd(fd) });
+
+        let dma_buf = vk::ExternalMemoryHandleTypeFlags::EXTERNAL_MEMORY_HANDLE_TYPE_DMA_BUF;
+        match memory.export_fd(dma_buf) {
+            Err(ExportMemoryError::NotExportable(handle_type)) => assert_eq!(handle_type, dma_buf),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "external_memory")]
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn export_requires_extension_and_excludes_import() {
+        let (device, _queue, _pool) = compute_device_with(|builder| builder);
+        let type_index = device
+            .find_memory_type_index(!0, vk::MemoryPropertyFlags::empty())
+            .unwrap();
+
+        let memory = MemoryBuilder::new(1024, type_index)
+            .build(device.clone())
+            .unwrap();
+        match memory.export_fd(OPAQUE_FD) {
+            Err(ExportMemoryError::ExtensionNotEnabled) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let builder = unsafe {
+            MemoryBuilder::new(1024, type_index)
+                .with_export(OPAQUE_FD)
+                .with_import_fd(-1, OPAQUE_FD)
+        };
+        match builder.build(device) {
+            Err(MemAllocError::ExportWithImport) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
     }
 }
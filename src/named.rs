@@ -0,0 +1,87 @@
+use std::fmt;
+use std::ops::Deref;
+
+/// Attaches human-readable name to a handle, e.g. for logging.
+/// Name is kept on the host only and isn't passed to Vulkan.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct Named<T> {
+    name: String,
+    inner: T,
+}
+
+impl<T> Named<T> {
+    pub fn new(inner: T, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            inner,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Deref for Named<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Named<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Named")
+            .field("name", &self.name)
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for Named<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferBuilder;
+    use crate::test_utils::compute_device;
+    use ash::vk;
+
+    #[test]
+    fn debug_shows_name_and_inner() {
+        let named = Named::new(42u32, "answer");
+        assert_eq!(
+            format!("{:?}", named),
+            "Named { name: \"answer\", inner: 42 }"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn buffer_debug_shows_name_and_handle() {
+        let (device, _queue, _pool) = compute_device();
+        let buffer = BufferBuilder::default()
+            .with_size(16)
+            .with_usage(vk::BufferUsageFlags::UNIFORM_BUFFER)
+            .build(device, &[])
+            .unwrap();
+        let handle = format!("{:?}", unsafe { *buffer.handle() });
+
+        let debug = format!("{:?}", Named::new(buffer, "uniforms"));
+        assert!(debug.contains("\"uniforms\""));
+        assert!(debug.contains(&handle));
+    }
+}
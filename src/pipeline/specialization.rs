@@ -0,0 +1,108 @@
+use ash::vk;
+
+/// Packs specialization constants to contiguous data blob with matching map entries.
+#[derive(Default)]
+pub struct SpecializationInfoBuilder {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl SpecializationInfoBuilder {
+    pub fn add_u32(self, constant_id: u32, value: u32) -> Self {
+        self.add_bytes(constant_id, &value.to_ne_bytes())
+    }
+
+    pub fn add_f32(self, constant_id: u32, value: f32) -> Self {
+        self.add_bytes(constant_id, &value.to_ne_bytes())
+    }
+
+    /// Boolean constants are stored as `VkBool32`.
+    pub fn add_bool(self, constant_id: u32, value: bool) -> Self {
+        let value = if value { vk::TRUE } else { vk::FALSE };
+        self.add_bytes(constant_id, &value.to_ne_bytes())
+    }
+
+    fn add_bytes(mut self, constant_id: u32, bytes: &[u8]) -> Self {
+        self.entries.push(vk::SpecializationMapEntry {
+            constant_id,
+            offset: self.data.len() as u32,
+            size: bytes.len(),
+        });
+        self.data.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn build(self) -> SpecializationInfo {
+        SpecializationInfo {
+            entries: self.entries,
+            data: self.data,
+        }
+    }
+}
+
+pub struct SpecializationInfo {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl SpecializationInfo {
+    pub fn entries(&self) -> &[vk::SpecializationMapEntry] {
+        &self.entries
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// # Safety
+    /// Returned struct points to `self` data, so it must not outlive `self`.
+    pub unsafe fn raw(&self) -> vk::SpecializationInfo {
+        vk::SpecializationInfo {
+            map_entry_count: self.entries.len() as u32,
+            p_map_entries: self.entries.as_ptr(),
+            data_size: self.data.len(),
+            p_data: self.data.as_ptr().cast(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_are_packed_contiguously() {
+        let info = SpecializationInfoBuilder::default()
+            .add_u32(0, 7)
+            .add_f32(3, 1.5)
+            .add_bool(1, true)
+            .build();
+
+        let entries: Vec<(u32, u32, usize)> = info
+            .entries()
+            .iter()
+            .map(|e| (e.constant_id, e.offset, e.size))
+            .collect();
+        assert_eq!(entries, vec![(0, 0, 4), (3, 4, 4), (1, 8, 4)]);
+        assert!(info.entries().iter().all(|e| e.offset % 4 == 0));
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&7u32.to_ne_bytes());
+        expected.extend_from_slice(&1.5f32.to_ne_bytes());
+        expected.extend_from_slice(&vk::TRUE.to_ne_bytes());
+        assert_eq!(info.data(), expected.as_slice());
+    }
+
+    #[test]
+    fn raw_points_to_owned_arrays() {
+        let info = SpecializationInfoBuilder::default()
+            .add_bool(0, false)
+            .build();
+        let raw = unsafe { info.raw() };
+
+        assert_eq!(raw.map_entry_count, 1);
+        assert_eq!(raw.p_map_entries, info.entries().as_ptr());
+        assert_eq!(raw.data_size, 4);
+        assert_eq!(raw.p_data, info.data().as_ptr().cast());
+    }
+}
@@ -1,8 +1,20 @@
+use crate::buffer::Buffer;
+use crate::command_buffer::{CommandBuffers, OneTimeSubmit, OneTimeSubmitResult};
+use crate::command_pool::CommandPool;
 use crate::device::Device;
+use crate::fence::{CreateFenceError, Fence, FenceBuilder};
+use crate::semaphore::Semaphore;
+#[cfg(feature = "surface")]
+use crate::swapchain::{Swapchain, SwapchainResult};
+use ash::prelude::VkResult;
 use ash::version::DeviceV1_0;
 use ash::vk;
 use std::error::Error;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Timeout of `Queue::submit_blocking`.
+pub const SUBMIT_BLOCKING_TIMEOUT_NS: u64 = 10_000_000_000;
 
 #[derive(Clone)]
 pub struct Queue {
@@ -15,6 +27,7 @@ pub struct Queue {
 impl Queue {
     pub fn get(device: Device, family_index: u32, queue_index: u32) -> Result<Self, GetQueueError> {
         log::trace!(
+            target: crate::LOG_TARGET,
             "Getting queue #{} with family #{} from device",
             queue_index,
             family_index
@@ -57,6 +70,241 @@ impl Queue {
     pub fn queue_index(&self) -> u32 {
         self.queue_index
     }
+
+    /// Copies `regions` from `src` to `dst` buffer and waits until copy is finished.
+    /// `pool` must be created for family of this queue.
+    pub fn copy_buffer_blocking(
+        &self,
+        pool: &CommandPool,
+        src: &Buffer,
+        dst: &Buffer,
+        regions: &[vk::BufferCopy],
+    ) -> OneTimeSubmitResult<()> {
+        let one_time_submit = OneTimeSubmit::begin(pool, &self.device)?;
+        unsafe {
+            self.device.handle().cmd_copy_buffer(
+                one_time_submit.recorder(),
+                *src.handle(),
+                *dst.handle(),
+                regions,
+            )
+        };
+        one_time_submit.submit_and_wait(self)
+    }
+
+    /// Submits `command_buffers` with temporary fence and waits for it.
+    pub(crate) unsafe fn submit_and_wait(
+        &self,
+        command_buffers: &[vk::CommandBuffer],
+    ) -> VkResult<()> {
+        let raw_device = self.device.handle();
+        let fence = raw_device.create_fence(
+            &vk::FenceCreateInfo::default(),
+            self.device.allocation_callbacks(),
+        )?;
+
+        let submit_info = vk::SubmitInfo {
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
+            ..Default::default()
+        };
+
+        let result = raw_device
+            .queue_submit(self.handle, &[submit_info], fence)
+            .and_then(|_| raw_device.wait_for_fences(&[fence], true, u64::MAX));
+        raw_device.destroy_fence(fence, self.device.allocation_callbacks());
+        self.device.track_lost(result)
+    }
+
+    /// Submits recorded `command_buffers` with temporary fence and waits for it
+    /// up to `SUBMIT_BLOCKING_TIMEOUT_NS`.
+    ///
+    /// On `Timeout` error submission is still pending and returned fence is signaled,
+    /// when it's completed. Until then the fence must not be reset or dropped
+    /// and `command_buffers` must not be reset or freed.
+    pub fn submit_blocking(&self, command_buffers: &[vk::CommandBuffer]) -> SubmitResult<()> {
+        let fence = FenceBuilder::default().build(self.device.clone())?;
+
+        let submit_info = vk::SubmitInfo {
+            command_buffer_count: command_buffers.len() as u32,
+            p_command_buffers: command_buffers.as_ptr(),
+            ..Default::default()
+        };
+        let result = unsafe {
+            self.device
+                .handle()
+                .queue_submit(self.handle, &[submit_info], *fence.handle())
+        };
+        self.device.track_lost(result)?;
+
+        if fence.wait(SUBMIT_BLOCKING_TIMEOUT_NS)? {
+            Ok(())
+        } else {
+            Err(SubmitError::Timeout(fence))
+        }
+    }
+
+    /// Submits all `submits` with single `vkQueueSubmit` call.
+    /// `fence` is signaled when all of them are completed.
+    ///
+    /// # Safety
+    /// Command buffers must be recorded and, with semaphores and fence,
+    /// must not be dropped until submission is completed.
+    pub unsafe fn submit_batch(
+        &self,
+        submits: &[SubmitDescriptor],
+        fence: Option<&Fence>,
+    ) -> VkResult<()> {
+        let submit_infos: Vec<vk::SubmitInfo> = submits.iter().map(SubmitDescriptor::raw).collect();
+        let fence = fence.map_or(vk::Fence::null(), |f| *f.handle());
+        let result = self
+            .device
+            .handle()
+            .queue_submit(self.handle, &submit_infos, fence);
+        self.device.track_lost(result)
+    }
+
+    /// Binds memory to sparse buffers and signals `fence` when binding is completed.
+    /// Queue family must support `SPARSE_BINDING`.
+    ///
+    /// # Safety
+    /// Buffers, memory and arrays, pointed by `buffer_binds`, must be alive until the call returns.
+    /// Buffers and memory must not be dropped until binding is completed.
+    pub unsafe fn bind_sparse(
+        &self,
+        buffer_binds: &[vk::SparseBufferMemoryBindInfo],
+        fence: Option<&Fence>,
+    ) -> VkResult<()> {
+        let bind_info = vk::BindSparseInfo {
+            buffer_bind_count: buffer_binds.len() as u32,
+            p_buffer_binds: buffer_binds.as_ptr(),
+            ..Default::default()
+        };
+        let fence = fence.map_or(vk::Fence::null(), |f| *f.handle());
+        // `DeviceV1_0` of ash 0.31 has no `queue_bind_sparse` wrapper.
+        let result = match self.device.handle().fp_v1_0().queue_bind_sparse(
+            self.handle,
+            1,
+            &bind_info,
+            fence,
+        ) {
+            vk::Result::SUCCESS => Ok(()),
+            e => Err(e),
+        };
+        self.device.track_lost(result)
+    }
+
+    /// Presents `image_indices[i]` image of `swapchains[i]` swapchain after all `wait` semaphores
+    /// are signaled. Returns `true` if any of swapchains is suboptimal for its surface.
+    ///
+    /// # Safety
+    /// `wait` semaphores must be created from queue's device and have signal operation pending.
+    #[cfg(feature = "surface")]
+    pub unsafe fn present(
+        &self,
+        swapchains: &[Swapchain],
+        image_indices: &[u32],
+        wait: &[Semaphore],
+    ) -> SwapchainResult<bool> {
+        assert_eq!(
+            swapchains.len(),
+            image_indices.len(),
+            "Each swapchain must have exactly one image index"
+        );
+
+        let loader = match swapchains.first() {
+            Some(swapchain) => swapchain.loader(),
+            None => return Ok(false),
+        };
+
+        let raw_swapchains: Vec<vk::SwapchainKHR> =
+            swapchains.iter().map(|s| *s.handle()).collect();
+        let raw_wait: Vec<vk::Semaphore> = wait.iter().map(|s| *s.handle()).collect();
+
+        let present_info = vk::PresentInfoKHR {
+            wait_semaphore_count: raw_wait.len() as u32,
+            p_wait_semaphores: raw_wait.as_ptr(),
+            swapchain_count: raw_swapchains.len() as u32,
+            p_swapchains: raw_swapchains.as_ptr(),
+            p_image_indices: image_indices.as_ptr(),
+            ..Default::default()
+        };
+
+        let result = loader.queue_present(self.handle, &present_info);
+        Ok(self.device.track_lost(result)?)
+    }
+}
+
+/// Command buffers with semaphores to wait before and signal after their execution.
+/// Used in `Queue::submit_batch`.
+#[derive(Clone, Default)]
+pub struct SubmitDescriptor {
+    command_buffers: Vec<vk::CommandBuffer>,
+    wait_semaphores: Vec<vk::Semaphore>,
+    wait_stages: Vec<vk::PipelineStageFlags>,
+    signal_semaphores: Vec<vk::Semaphore>,
+}
+
+impl SubmitDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds all command buffers of `command_buffers`.
+    pub fn with_command_buffers(mut self, command_buffers: &CommandBuffers) -> Self {
+        let handles = (0..command_buffers.len())
+            .filter_map(|index| unsafe { command_buffers.handle(index) })
+            .copied();
+        self.command_buffers.extend(handles);
+        self
+    }
+
+    /// Execution of `stage` waits until `semaphore` is signaled.
+    pub fn with_wait(mut self, semaphore: &Semaphore, stage: vk::PipelineStageFlags) -> Self {
+        self.wait_semaphores.push(unsafe { *semaphore.handle() });
+        self.wait_stages.push(stage);
+        self
+    }
+
+    pub fn with_signal(mut self, semaphore: &Semaphore) -> Self {
+        self.signal_semaphores.push(unsafe { *semaphore.handle() });
+        self
+    }
+
+    /// Returned value borrows `self`.
+    fn raw(&self) -> vk::SubmitInfo {
+        vk::SubmitInfo {
+            wait_semaphore_count: self.wait_semaphores.len() as u32,
+            p_wait_semaphores: self.wait_semaphores.as_ptr(),
+            p_wait_dst_stage_mask: self.wait_stages.as_ptr(),
+            command_buffer_count: self.command_buffers.len() as u32,
+            p_command_buffers: self.command_buffers.as_ptr(),
+            signal_semaphore_count: self.signal_semaphores.len() as u32,
+            p_signal_semaphores: self.signal_semaphores.as_ptr(),
+            ..Default::default()
+        }
+    }
+}
+
+impl fmt::Display for Queue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Queue(family {}, index {}) of {}",
+            self.family_index, self.queue_index, self.device
+        )
+    }
+}
+
+impl fmt::Debug for Queue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("handle", unsafe { self.handle() })
+            .field("family_index", &self.family_index)
+            .field("queue_index", &self.queue_index)
+            .field("device", &self.device)
+            .finish()
+    }
 }
 
 impl Eq for Queue {}
@@ -67,6 +315,12 @@ impl PartialEq for Queue {
     }
 }
 
+impl Hash for Queue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 pub type GetQueueResult<T> = Result<T, GetQueueError>;
 
 #[derive(Debug)]
@@ -91,3 +345,202 @@ impl fmt::Display for GetQueueError {
         }
     }
 }
+
+pub type SubmitResult<T> = Result<T, SubmitError>;
+
+#[derive(Debug)]
+pub enum SubmitError {
+    VkError(vk::Result),
+    /// Device is lost and must be recreated with all its objects.
+    DeviceLost,
+    /// Submission wasn't completed in time and is still pending until the fence is signaled.
+    Timeout(Fence),
+}
+
+impl Error for SubmitError {}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't submit command buffers: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't submit command buffers: device lost"),
+            Self::Timeout(_) => write!(
+                f,
+                "Submitted command buffers weren't executed in {} ns",
+                SUBMIT_BLOCKING_TIMEOUT_NS
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for SubmitError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+impl From<CreateFenceError> for SubmitError {
+    fn from(e: CreateFenceError) -> Self {
+        match e {
+            CreateFenceError::VkError(e) => Self::VkError(e),
+            CreateFenceError::DeviceLost => Self::DeviceLost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::BufferBuilder;
+    use crate::command_buffer::CommandBuffersBuilder;
+    use crate::device::pdevice_selectors;
+    use crate::memory::MemoryBuilder;
+    use crate::test_utils::{compute_device, device_with};
+
+    /// Allocates `count` command buffers from `pool` and records nothing to them.
+    fn record_noop(device: &Device, pool: &CommandPool, count: u32) -> CommandBuffers {
+        let command_buffers = CommandBuffersBuilder::default()
+            .with_count(count)
+            .build(pool.clone(), device.clone())
+            .unwrap();
+        for index in 0..command_buffers.len() {
+            unsafe {
+                let handle = *command_buffers.handle(index).unwrap();
+                device
+                    .handle()
+                    .begin_command_buffer(handle, &vk::CommandBufferBeginInfo::default())
+                    .unwrap();
+                device.handle().end_command_buffer(handle).unwrap();
+            }
+        }
+        command_buffers
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn submit_blocking_waits_for_noop() {
+        let (device, queue, pool) = compute_device();
+        let command_buffers = record_noop(&device, &pool, 1);
+
+        let handles = [unsafe { *command_buffers.handle(0).unwrap() }];
+        queue.submit_blocking(&handles).unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn batch_of_two_submits_signals_single_fence() {
+        let (device, queue, pool) = compute_device();
+        let first = record_noop(&device, &pool, 1);
+        let second = record_noop(&device, &pool, 2);
+        let fence = FenceBuilder::default().build(device).unwrap();
+
+        let submits = [
+            SubmitDescriptor::new().with_command_buffers(&first),
+            SubmitDescriptor::new().with_command_buffers(&second),
+        ];
+        unsafe { queue.submit_batch(&submits, Some(&fence)).unwrap() };
+        assert!(fence.wait(u64::MAX).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device with sparse binding support"]
+    fn single_sparse_block_is_bound() {
+        let (device, queue, _pool) =
+            device_with(Box::new(pdevice_selectors::any_sparse_binding), |builder| {
+                builder
+            });
+        let probe = BufferBuilder::default()
+            .with_size(1)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .with_flags(vk::BufferCreateFlags::SPARSE_BINDING)
+            .build(device.clone(), &[])
+            .unwrap();
+        let block_size = probe.memory_requirements().alignment;
+
+        let buffer = BufferBuilder::default()
+            .with_size(block_size)
+            .with_usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .with_flags(vk::BufferCreateFlags::SPARSE_BINDING)
+            .build(device.clone(), &[])
+            .unwrap();
+        let requirements = buffer.memory_requirements();
+        let type_index = device
+            .find_memory_type_index(
+                requirements.memory_type_bits,
+                vk::MemoryPropertyFlags::empty(),
+            )
+            .unwrap();
+        let memory = MemoryBuilder::new(requirements.size, type_index)
+            .build(device.clone())
+            .unwrap();
+        let fence = FenceBuilder::default().build(device).unwrap();
+
+        unsafe {
+            let binds = [vk::SparseMemoryBind {
+                resource_offset: 0,
+                size: requirements.size,
+                memory: *memory.handle(),
+                memory_offset: 0,
+                flags: vk::SparseMemoryBindFlags::empty(),
+            }];
+            let buffer_binds = [vk::SparseBufferMemoryBindInfo {
+                buffer: *buffer.handle(),
+                bind_count: binds.len() as u32,
+                p_binds: binds.as_ptr(),
+            }];
+            queue.bind_sparse(&buffer_binds, Some(&fence)).unwrap();
+        }
+        assert!(fence.wait(u64::MAX).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn copy_between_host_visible_buffers() {
+        let (device, queue, pool) = compute_device();
+        let host_buffer = |usage| {
+            BufferBuilder::default()
+                .with_size(16)
+                .with_usage(usage)
+                .build_with_memory(
+                    device.clone(),
+                    &[],
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                )
+                .unwrap()
+        };
+        let (src, src_memory) = host_buffer(vk::BufferUsageFlags::TRANSFER_SRC);
+        let (dst, dst_memory) = host_buffer(vk::BufferUsageFlags::TRANSFER_DST);
+
+        let pattern: Vec<u8> = (0..16).map(|i| i * 3).collect();
+        unsafe {
+            let ptr = src_memory.map(0, 16).unwrap().cast::<u8>();
+            ptr.copy_from_nonoverlapping(pattern.as_ptr(), pattern.len());
+            src_memory.unmap();
+        }
+
+        let region = vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: 0,
+            size: 16,
+        };
+        queue
+            .copy_buffer_blocking(&pool, &src, &dst, &[region])
+            .unwrap();
+
+        let copied = unsafe {
+            let ptr = dst_memory.map(0, 16).unwrap().cast::<u8>();
+            let copied = std::slice::from_raw_parts(ptr, 16).to_vec();
+            dst_memory.unmap();
+            copied
+        };
+        assert_eq!(copied, pattern);
+    }
+}
@@ -1,15 +1,58 @@
 use crate::device::Device;
 use ash::version::DeviceV1_0;
 use ash::vk;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
-use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
 
 pub struct SamplerBuilder {
     create_info: vk::SamplerCreateInfo,
+    reduction_mode_info: Option<vk::SamplerReductionModeCreateInfo>,
+}
+
+/// Linear filtering, repeat addressing, single LOD and no anisotropy.
+impl Default for SamplerBuilder {
+    fn default() -> Self {
+        Self {
+            create_info: vk::SamplerCreateInfo {
+                mag_filter: vk::Filter::LINEAR,
+                min_filter: vk::Filter::LINEAR,
+                mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+                address_mode_u: vk::SamplerAddressMode::REPEAT,
+                address_mode_v: vk::SamplerAddressMode::REPEAT,
+                address_mode_w: vk::SamplerAddressMode::REPEAT,
+                anisotropy_enable: vk::FALSE,
+                max_anisotropy: 1.0,
+                min_lod: 0.0,
+                max_lod: 0.0,
+                ..Default::default()
+            },
+            reduction_mode_info: None,
+        }
+    }
 }
 
 impl SamplerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Depth comparison sampler with `LESS_OR_EQUAL` compare op and clamped addressing.
+    pub fn shadow_map() -> Self {
+        let clamp = vk::SamplerAddressMode::CLAMP_TO_EDGE;
+        Self::default()
+            .with_compare_op(Some(vk::CompareOp::LESS_OR_EQUAL))
+            .with_address_modes(clamp, clamp, clamp)
+            .with_border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+    }
+
+    pub fn with_flags(mut self, flags: vk::SamplerCreateFlags) -> Self {
+        self.create_info.flags = flags;
+        self
+    }
+
     pub fn with_min_mag_filters(mut self, min: vk::Filter, mag: vk::Filter) -> Self {
         self.create_info.min_filter = min;
         self.create_info.mag_filter = mag;
@@ -77,20 +120,133 @@ impl SamplerBuilder {
         self
     }
 
+    /// Requires `samplerFilterMinmax` feature (Vulkan 1.2 or `VK_EXT_sampler_filter_minmax`).
+    pub fn with_reduction_mode(mut self, reduction_mode: vk::SamplerReductionMode) -> Self {
+        self.reduction_mode_info = Some(vk::SamplerReductionModeCreateInfo {
+            reduction_mode,
+            ..Default::default()
+        });
+        self
+    }
+
     pub fn build(self, device: Device) -> CreateSamplerResult<Sampler> {
-        unsafe { Sampler::new(&self.create_info, device) }
+        if let Some(reduction_mode_info) = &self.reduction_mode_info {
+            log::trace!(
+                target: crate::LOG_TARGET,
+                "Sampler reduction mode: {:?}",
+                reduction_mode_info.reduction_mode
+            );
+        }
+
+        let create_info = self.raw_create_info();
+        unsafe { Sampler::new(&create_info, device) }
+    }
+
+    /// Returned value borrows `self`.
+    fn raw_create_info(&self) -> vk::SamplerCreateInfo {
+        let mut create_info = self.create_info;
+        if let Some(reduction_mode_info) = &self.reduction_mode_info {
+            create_info.p_next =
+                (reduction_mode_info as *const vk::SamplerReductionModeCreateInfo).cast();
+        }
+        create_info
     }
 }
 
-impl Default for SamplerBuilder {
-    fn default() -> Self {
-        SamplerBuilder {
-            create_info: Default::default(),
+/// Hashable projection of sampler parameters. Floats are compared by bit patterns,
+/// except zeros: `-0.0` and `0.0` are equal.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SamplerKey {
+    flags: vk::SamplerCreateFlags,
+    filters: [vk::Filter; 2],
+    mipmap_mode: vk::SamplerMipmapMode,
+    address_modes: [vk::SamplerAddressMode; 3],
+    mip_lod_bias: u32,
+    anisotropy_enable: vk::Bool32,
+    max_anisotropy: u32,
+    compare_enable: vk::Bool32,
+    compare_op: vk::CompareOp,
+    lod: [u32; 2],
+    border_color: vk::BorderColor,
+    unnormalized_coordinates: vk::Bool32,
+    reduction_mode: Option<vk::SamplerReductionMode>,
+}
+
+impl From<&SamplerBuilder> for SamplerKey {
+    fn from(builder: &SamplerBuilder) -> Self {
+        let info = &builder.create_info;
+        Self {
+            flags: info.flags,
+            filters: [info.min_filter, info.mag_filter],
+            mipmap_mode: info.mipmap_mode,
+            address_modes: [
+                info.address_mode_u,
+                info.address_mode_v,
+                info.address_mode_w,
+            ],
+            mip_lod_bias: float_key(info.mip_lod_bias),
+            anisotropy_enable: info.anisotropy_enable,
+            max_anisotropy: float_key(info.max_anisotropy),
+            compare_enable: info.compare_enable,
+            compare_op: info.compare_op,
+            lod: [float_key(info.min_lod), float_key(info.max_lod)],
+            border_color: info.border_color,
+            unnormalized_coordinates: info.unnormalized_coordinates,
+            reduction_mode: builder.reduction_mode_info.map(|info| info.reduction_mode),
+        }
+    }
+}
+
+/// Bit pattern of `value` with single representation of zero.
+fn float_key(value: f32) -> u32 {
+    if value == 0.0 {
+        0
+    } else {
+        value.to_bits()
+    }
+}
+
+/// Creates samplers once for each set of parameters and returns cached ones afterwards.
+pub struct SamplerCache {
+    device: Device,
+    samplers: Mutex<HashMap<SamplerKey, Sampler>>,
+}
+
+impl SamplerCache {
+    pub fn new(device: Device) -> Self {
+        Self {
+            device,
+            samplers: Default::default(),
+        }
+    }
+
+    /// Returns cached sampler with parameters of `builder` or builds new one.
+    pub fn get_or_create(&self, builder: SamplerBuilder) -> CreateSamplerResult<Sampler> {
+        let key = SamplerKey::from(&builder);
+        let mut samplers = self.samplers.lock().unwrap();
+        if let Some(sampler) = samplers.get(&key) {
+            return Ok(sampler.clone());
         }
+
+        let sampler = builder.build(self.device.clone())?;
+        samplers.insert(key, sampler.clone());
+        Ok(sampler)
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
-#[derive(Clone, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Sampler {
     sampler: Arc<UniqueSampler>,
 }
@@ -110,11 +266,22 @@ impl Sampler {
     /// # Safety
     /// todo
     pub unsafe fn handle(&self) -> &vk::Sampler {
-        &self.sampler.handle()
+        self.sampler.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.sampler)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.sampler, &other.sampler)
     }
 
     pub fn device(&self) -> &Device {
-        &self.sampler.device()
+        self.sampler.device()
     }
 }
 
@@ -128,8 +295,10 @@ impl UniqueSampler {
         create_info: &vk::SamplerCreateInfo,
         device: Device,
     ) -> CreateSamplerResult<Self> {
-        log::trace!("Creating vulkan sampler");
-        let handle = device.handle().create_sampler(create_info, None)?;
+        log::trace!(target: crate::LOG_TARGET, "Creating vulkan sampler");
+        let handle = device
+            .handle()
+            .create_sampler(create_info, device.allocation_callbacks())?;
         Ok(Self { handle, device })
     }
 
@@ -144,8 +313,20 @@ impl UniqueSampler {
 
 impl Drop for UniqueSampler {
     fn drop(&mut self) {
-        log::trace!("Destroying vulkan sampler");
-        unsafe { self.device.handle().destroy_sampler(self.handle, None) }
+        log::trace!(target: crate::LOG_TARGET, "Destroying vulkan sampler");
+        unsafe {
+            self.device
+                .handle()
+                .destroy_sampler(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueSampler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sampler")
+            .field("handle", &self.handle)
+            .finish()
     }
 }
 
@@ -157,11 +338,18 @@ impl PartialEq for UniqueSampler {
     }
 }
 
+impl Hash for UniqueSampler {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
 pub type CreateSamplerResult<T> = Result<T, CreateSamplerError>;
 
 #[derive(Debug)]
 pub enum CreateSamplerError {
     VkError(vk::Result),
+    DeviceLost,
 }
 
 impl Error for CreateSamplerError {}
@@ -169,13 +357,151 @@ impl Error for CreateSamplerError {}
 impl fmt::Display for CreateSamplerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::VkError(e) => write!(f, "Can't create vk sampler: {}", e),
+            Self::VkError(e) => write!(
+                f,
+                "Can't create vk sampler: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create vk sampler: device lost"),
         }
     }
 }
 
 impl From<vk::Result> for CreateSamplerError {
     fn from(e: vk::Result) -> Self {
-        Self::VkError(e)
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{compute_device, compute_device_with};
+
+    #[test]
+    fn default_builder_has_documented_values() {
+        let info = SamplerBuilder::new().create_info;
+        assert_eq!(info.mag_filter, vk::Filter::LINEAR);
+        assert_eq!(info.min_filter, vk::Filter::LINEAR);
+        assert_eq!(info.mipmap_mode, vk::SamplerMipmapMode::LINEAR);
+        assert_eq!(info.address_mode_u, vk::SamplerAddressMode::REPEAT);
+        assert_eq!(info.address_mode_v, vk::SamplerAddressMode::REPEAT);
+        assert_eq!(info.address_mode_w, vk::SamplerAddressMode::REPEAT);
+        assert_eq!(info.anisotropy_enable, vk::FALSE);
+        assert_eq!(info.max_anisotropy, 1.0);
+        assert_eq!((info.min_lod, info.max_lod), (0.0, 0.0));
+        assert_eq!(info.compare_enable, vk::FALSE);
+    }
+
+    #[test]
+    fn flags_are_empty_by_default() {
+        assert!(SamplerBuilder::new().create_info.flags.is_empty());
+
+        let flags = vk::SamplerCreateFlags::SUBSAMPLED_EXT;
+        let info = SamplerBuilder::new().with_flags(flags).raw_create_info();
+        assert_eq!(info.flags, flags);
+    }
+
+    #[test]
+    fn shadow_map_compares_depth() {
+        let info = SamplerBuilder::shadow_map().create_info;
+        assert_eq!(info.compare_enable, vk::TRUE);
+        assert_eq!(info.compare_op, vk::CompareOp::LESS_OR_EQUAL);
+        assert_eq!(info.mag_filter, vk::Filter::LINEAR);
+        assert_eq!(info.min_filter, vk::Filter::LINEAR);
+        assert_eq!(info.address_mode_u, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        assert_eq!(info.address_mode_v, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        assert_eq!(info.address_mode_w, vk::SamplerAddressMode::CLAMP_TO_EDGE);
+        assert_eq!(info.border_color, vk::BorderColor::FLOAT_OPAQUE_WHITE);
+    }
+
+    #[test]
+    fn equal_builders_have_equal_keys() {
+        let key = |builder: SamplerBuilder| SamplerKey::from(&builder);
+        assert_eq!(
+            key(SamplerBuilder::shadow_map()),
+            key(SamplerBuilder::shadow_map())
+        );
+        assert_eq!(
+            key(SamplerBuilder::new().with_mip_lod_bias(0.0)),
+            key(SamplerBuilder::new().with_mip_lod_bias(-0.0))
+        );
+        assert_eq!(
+            key(SamplerBuilder::new().with_min_max_lod(-0.0, 1.0)),
+            key(SamplerBuilder::new().with_min_max_lod(0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn different_builders_have_different_keys() {
+        let key = |builder: SamplerBuilder| SamplerKey::from(&builder);
+        let min = vk::SamplerReductionMode::MIN;
+        let max = vk::SamplerReductionMode::MAX;
+        let default = key(SamplerBuilder::new());
+        assert_ne!(default, key(SamplerBuilder::new().with_mip_lod_bias(0.5)));
+        assert_ne!(
+            default,
+            key(SamplerBuilder::new().with_max_anisotropy(16.0))
+        );
+        assert_ne!(
+            default,
+            key(SamplerBuilder::new().with_min_max_lod(0.0, 1.0))
+        );
+        assert_ne!(default, key(SamplerBuilder::new().with_reduction_mode(min)));
+        assert_ne!(
+            key(SamplerBuilder::new().with_reduction_mode(min)),
+            key(SamplerBuilder::new().with_reduction_mode(max))
+        );
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn identical_requests_share_sampler() {
+        let (device, _queue, _pool) = compute_device();
+        let cache = SamplerCache::new(device);
+
+        let first = cache.get_or_create(SamplerBuilder::shadow_map()).unwrap();
+        let second = cache.get_or_create(SamplerBuilder::shadow_map()).unwrap();
+        let other = cache.get_or_create(SamplerBuilder::new()).unwrap();
+
+        assert!(first.same_instance(&second));
+        assert!(!first.same_instance(&other));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn reduction_mode_is_chained() {
+        let builder = SamplerBuilder::new().with_reduction_mode(vk::SamplerReductionMode::MIN);
+        let info = builder.raw_create_info();
+
+        let reduction = info.p_next as *const vk::SamplerReductionModeCreateInfo;
+        assert_eq!(
+            unsafe { (*reduction).reduction_mode },
+            vk::SamplerReductionMode::MIN
+        );
+        assert_eq!(
+            SamplerKey::from(&builder).reduction_mode,
+            Some(vk::SamplerReductionMode::MIN)
+        );
+        assert!(SamplerBuilder::new().raw_create_info().p_next.is_null());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device with samplerFilterMinmax feature"]
+    fn min_reduction_sampler_is_created() {
+        let (device, _queue, _pool) = compute_device_with(|builder| {
+            builder.with_feature_struct(vk::PhysicalDeviceVulkan12Features {
+                sampler_filter_minmax: vk::TRUE,
+                ..Default::default()
+            })
+        });
+        SamplerBuilder::new()
+            .with_reduction_mode(vk::SamplerReductionMode::MIN)
+            .build(device)
+            .unwrap();
     }
 }
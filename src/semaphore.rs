@@ -0,0 +1,414 @@
+use crate::device::Device;
+use ash::version::{DeviceV1_0, DeviceV1_2};
+use ash::vk;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+#[derive(Default)]
+pub struct SemaphoreBuilder {
+    type_create_info: Option<vk::SemaphoreTypeCreateInfo>,
+}
+
+impl SemaphoreBuilder {
+    /// Makes timeline semaphore with `initial_value` counter.
+    /// Device must be created with Vulkan 1.2 API and `timelineSemaphore` feature enabled.
+    pub fn with_timeline(mut self, initial_value: u64) -> Self {
+        self.type_create_info = Some(vk::SemaphoreTypeCreateInfo {
+            semaphore_type: vk::SemaphoreType::TIMELINE,
+            initial_value,
+            ..Default::default()
+        });
+        self
+    }
+
+    pub fn build(self, device: Device) -> CreateSemaphoreResult<Semaphore> {
+        let mut create_info = vk::SemaphoreCreateInfo::default();
+        if let Some(type_create_info) = &self.type_create_info {
+            create_info.p_next = (type_create_info as *const vk::SemaphoreTypeCreateInfo).cast();
+        }
+
+        unsafe { Semaphore::new(device, &create_info) }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Semaphore {
+    unique_semaphore: Arc<UniqueSemaphore>,
+}
+
+impl Semaphore {
+    /// # Safety
+    /// todo
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::SemaphoreCreateInfo,
+    ) -> CreateSemaphoreResult<Self> {
+        UniqueSemaphore::new(device, create_info).map(|us| Self {
+            unique_semaphore: Arc::new(us),
+        })
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn handle(&self) -> &vk::Semaphore {
+        self.unique_semaphore.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_semaphore)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_semaphore, &other.unique_semaphore)
+    }
+
+    pub fn device(&self) -> &Device {
+        self.unique_semaphore.device()
+    }
+
+    pub fn semaphore_type(&self) -> vk::SemaphoreType {
+        self.unique_semaphore.semaphore_type()
+    }
+
+    pub fn is_timeline(&self) -> bool {
+        self.semaphore_type() == vk::SemaphoreType::TIMELINE
+    }
+
+    fn check_timeline(&self) -> TimelineResult<()> {
+        if self.is_timeline() {
+            Ok(())
+        } else {
+            Err(TimelineError::NotTimeline)
+        }
+    }
+
+    /// Sets timeline semaphore counter to `value` from host.
+    /// Returns `NotTimeline` error if semaphore isn't timeline one.
+    pub fn signal(&self, value: u64) -> TimelineResult<()> {
+        self.check_timeline()?;
+        let signal_info = vk::SemaphoreSignalInfo {
+            semaphore: unsafe { *self.handle() },
+            value,
+            ..Default::default()
+        };
+        let result = unsafe { self.device().handle().signal_semaphore(&signal_info) };
+        Ok(self.device().track_lost(result)?)
+    }
+
+    /// Waits until timeline semaphore counter reaches `value`.
+    /// Returns `false` if timeout expired before that.
+    /// Returns `NotTimeline` error if semaphore isn't timeline one.
+    pub fn wait(&self, value: u64, timeout_ns: u64) -> TimelineResult<bool> {
+        self.check_timeline()?;
+        let semaphores = [unsafe { *self.handle() }];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo {
+            semaphore_count: 1,
+            p_semaphores: semaphores.as_ptr(),
+            p_values: values.as_ptr(),
+            ..Default::default()
+        };
+
+        let result = unsafe {
+            self.device()
+                .handle()
+                .wait_semaphores(&wait_info, timeout_ns)
+        };
+
+        match self.device().track_lost(result) {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns current timeline semaphore counter value.
+    /// Returns `NotTimeline` error if semaphore isn't timeline one.
+    pub fn counter_value(&self) -> TimelineResult<u64> {
+        self.check_timeline()?;
+        let value = unsafe {
+            self.device()
+                .handle()
+                .get_semaphore_counter_value(*self.handle())
+        };
+        Ok(self.device().track_lost(value)?)
+    }
+}
+
+/// Waits for timeline semaphores to reach their target values.
+/// If `wait_all` is `true`, waits until all `semaphores` reach their values,
+/// else waits until at least one of them does.
+/// Returns `false` if timeout expired before wait condition was satisfied.
+/// Returns `true` immediately if `semaphores` is empty.
+/// Returns `NotTimeline` error if any of semaphores isn't timeline one.
+pub fn wait_timeline(
+    device: &Device,
+    semaphores: &[(Semaphore, u64)],
+    wait_all: bool,
+    timeout_ns: u64,
+) -> TimelineResult<bool> {
+    if semaphores.is_empty() {
+        return Ok(true);
+    }
+
+    let raw_semaphores = semaphores
+        .iter()
+        .map(|(s, _)| s.check_timeline().map(|()| unsafe { *s.handle() }))
+        .collect::<TimelineResult<Vec<vk::Semaphore>>>()?;
+    let values: Vec<u64> = semaphores.iter().map(|(_, v)| *v).collect();
+    let flags = if wait_all {
+        vk::SemaphoreWaitFlags::empty()
+    } else {
+        vk::SemaphoreWaitFlags::ANY
+    };
+    let wait_info = vk::SemaphoreWaitInfo {
+        flags,
+        semaphore_count: raw_semaphores.len() as u32,
+        p_semaphores: raw_semaphores.as_ptr(),
+        p_values: values.as_ptr(),
+        ..Default::default()
+    };
+
+    let result = unsafe { device.handle().wait_semaphores(&wait_info, timeout_ns) };
+
+    match device.track_lost(result) {
+        Ok(()) => Ok(true),
+        Err(vk::Result::TIMEOUT) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+struct UniqueSemaphore {
+    handle: vk::Semaphore,
+    device: Device,
+    semaphore_type: vk::SemaphoreType,
+}
+
+impl UniqueSemaphore {
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::SemaphoreCreateInfo,
+    ) -> CreateSemaphoreResult<Self> {
+        log::trace!(target: crate::LOG_TARGET, "Creating semaphore");
+        let handle = device
+            .handle()
+            .create_semaphore(create_info, device.allocation_callbacks())?;
+        Ok(Self {
+            handle,
+            device,
+            semaphore_type: semaphore_type(create_info),
+        })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::Semaphore {
+        &self.handle
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn semaphore_type(&self) -> vk::SemaphoreType {
+        self.semaphore_type
+    }
+}
+
+/// Finds semaphore type in `p_next` chain of `create_info`. Semaphore is binary by default.
+///
+/// # Safety
+/// `p_next` chain of `create_info` must consist of valid Vulkan structures.
+unsafe fn semaphore_type(create_info: &vk::SemaphoreCreateInfo) -> vk::SemaphoreType {
+    let mut next = create_info.p_next as *const vk::BaseInStructure;
+    while !next.is_null() {
+        if (*next).s_type == vk::StructureType::SEMAPHORE_TYPE_CREATE_INFO {
+            return (*next.cast::<vk::SemaphoreTypeCreateInfo>()).semaphore_type;
+        }
+        next = (*next).p_next;
+    }
+    vk::SemaphoreType::BINARY
+}
+
+impl Drop for UniqueSemaphore {
+    fn drop(&mut self) {
+        log::trace!(target: crate::LOG_TARGET, "Destroying semaphore");
+        unsafe {
+            self.device
+                .handle()
+                .destroy_semaphore(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueSemaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Eq for UniqueSemaphore {}
+
+impl PartialEq for UniqueSemaphore {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueSemaphore {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+pub type CreateSemaphoreResult<T> = Result<T, CreateSemaphoreError>;
+
+#[derive(Debug)]
+pub enum CreateSemaphoreError {
+    VkError(vk::Result),
+    DeviceLost,
+}
+
+impl Error for CreateSemaphoreError {}
+
+impl fmt::Display for CreateSemaphoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't create semaphore: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create semaphore: device lost"),
+        }
+    }
+}
+
+impl From<vk::Result> for CreateSemaphoreError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+pub type TimelineResult<T> = Result<T, TimelineError>;
+
+#[derive(Debug)]
+pub enum TimelineError {
+    VkError(vk::Result),
+    DeviceLost,
+    NotTimeline,
+}
+
+impl Error for TimelineError {}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Timeline semaphore operation failed: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Timeline semaphore operation failed: device lost"),
+            Self::NotTimeline => write!(f, "Semaphore must be created with TIMELINE type"),
+        }
+    }
+}
+
+impl From<vk::Result> for TimelineError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device_with;
+
+    fn timeline_device() -> Device {
+        let features = vk::PhysicalDeviceVulkan12Features {
+            timeline_semaphore: vk::TRUE,
+            ..Default::default()
+        };
+        compute_device_with(|builder| builder.with_feature_struct(features)).0
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device"]
+    fn timeline_is_signaled_from_host() {
+        let device = timeline_device();
+        let semaphore = SemaphoreBuilder::default()
+            .with_timeline(0)
+            .build(device)
+            .unwrap();
+        assert!(semaphore.is_timeline());
+
+        for value in 1..4 {
+            semaphore.signal(value).unwrap();
+            assert!(semaphore.wait(value, 0).unwrap());
+            assert_eq!(semaphore.counter_value().unwrap(), value);
+        }
+        assert!(!semaphore.wait(10, 0).unwrap());
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device"]
+    fn binary_semaphore_cant_be_signaled_from_host() {
+        let device = timeline_device();
+        let semaphore = SemaphoreBuilder::default().build(device.clone()).unwrap();
+        assert!(!semaphore.is_timeline());
+
+        assert!(matches!(
+            semaphore.signal(1),
+            Err(TimelineError::NotTimeline)
+        ));
+        assert!(matches!(
+            semaphore.wait(1, 0),
+            Err(TimelineError::NotTimeline)
+        ));
+        assert!(matches!(
+            semaphore.counter_value(),
+            Err(TimelineError::NotTimeline)
+        ));
+        assert!(matches!(
+            wait_timeline(&device, &[(semaphore, 1)], true, 0),
+            Err(TimelineError::NotTimeline)
+        ));
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan 1.2 device"]
+    fn two_timelines_are_waited() {
+        let device = timeline_device();
+        let first = SemaphoreBuilder::default()
+            .with_timeline(0)
+            .build(device.clone())
+            .unwrap();
+        let second = SemaphoreBuilder::default()
+            .with_timeline(0)
+            .build(device.clone())
+            .unwrap();
+        let semaphores = [(first.clone(), 1), (second.clone(), 2)];
+
+        first.signal(1).unwrap();
+        assert!(wait_timeline(&device, &semaphores, false, 0).unwrap());
+        assert!(!wait_timeline(&device, &semaphores, true, 0).unwrap());
+
+        second.signal(2).unwrap();
+        assert!(wait_timeline(&device, &semaphores, true, 0).unwrap());
+    }
+}
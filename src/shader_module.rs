@@ -0,0 +1,254 @@
+use crate::device::Device;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+pub struct ShaderModuleBuilder {
+    code: Vec<u32>,
+}
+
+impl ShaderModuleBuilder {
+    pub fn new(code: Vec<u32>) -> Self {
+        Self { code }
+    }
+
+    /// Reads SPIR-V words from bytes, e.g. from content of `.spv` file.
+    /// Words byte order is detected by SPIR-V magic number.
+    pub fn from_bytes(bytes: &[u8]) -> ShaderModuleResult<Self> {
+        if bytes.len() < 4 || bytes.len() % 4 != 0 {
+            return Err(ShaderModuleError::BadCodeSize(bytes.len()));
+        }
+
+        let words = bytes.chunks_exact(4).map(|w| w.try_into().unwrap());
+        let magic = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let code = if magic == SPIRV_MAGIC {
+            words.map(u32::from_le_bytes).collect()
+        } else if magic.swap_bytes() == SPIRV_MAGIC {
+            words.map(u32::from_be_bytes).collect()
+        } else {
+            return Err(ShaderModuleError::BadMagicNumber(magic));
+        };
+
+        Ok(Self { code })
+    }
+
+    pub fn build(self, device: Device) -> ShaderModuleResult<ShaderModule> {
+        let create_info = vk::ShaderModuleCreateInfo {
+            code_size: self.code.len() * 4,
+            p_code: self.code.as_ptr(),
+            ..Default::default()
+        };
+
+        unsafe { ShaderModule::new(device, &create_info) }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ShaderModule {
+    unique_shader_module: Arc<UniqueShaderModule>,
+}
+
+impl ShaderModule {
+    /// # Safety
+    /// todo
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::ShaderModuleCreateInfo,
+    ) -> ShaderModuleResult<Self> {
+        UniqueShaderModule::new(device, create_info).map(|usm| Self {
+            unique_shader_module: Arc::new(usm),
+        })
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn handle(&self) -> &vk::ShaderModule {
+        self.unique_shader_module.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_shader_module)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_shader_module, &other.unique_shader_module)
+    }
+
+    pub fn device(&self) -> &Device {
+        self.unique_shader_module.device()
+    }
+
+    pub fn code_size(&self) -> usize {
+        self.unique_shader_module.code_size()
+    }
+}
+
+struct UniqueShaderModule {
+    handle: vk::ShaderModule,
+    device: Device,
+    code_size: usize,
+}
+
+impl UniqueShaderModule {
+    pub unsafe fn new(
+        device: Device,
+        create_info: &vk::ShaderModuleCreateInfo,
+    ) -> ShaderModuleResult<Self> {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Creating shader module with code size: {} bytes ({} words)",
+            create_info.code_size,
+            create_info.code_size / 4
+        );
+        let handle = device
+            .handle()
+            .create_shader_module(create_info, device.allocation_callbacks())?;
+        Ok(Self {
+            handle,
+            device,
+            code_size: create_info.code_size,
+        })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::ShaderModule {
+        &self.handle
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn code_size(&self) -> usize {
+        self.code_size
+    }
+}
+
+impl Drop for UniqueShaderModule {
+    fn drop(&mut self) {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Destroying shader module with code size: {} bytes",
+            self.code_size
+        );
+        unsafe {
+            self.device
+                .handle()
+                .destroy_shader_module(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueShaderModule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShaderModule")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Eq for UniqueShaderModule {}
+
+impl PartialEq for UniqueShaderModule {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueShaderModule {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+pub type ShaderModuleResult<T> = Result<T, ShaderModuleError>;
+
+#[derive(Debug)]
+pub enum ShaderModuleError {
+    VkError(vk::Result),
+    DeviceLost,
+    BadCodeSize(usize),
+    BadMagicNumber(u32),
+}
+
+impl Error for ShaderModuleError {}
+
+impl fmt::Display for ShaderModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't create shader module: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::DeviceLost => write!(f, "Can't create shader module: device lost"),
+            Self::BadCodeSize(size) => write!(
+                f,
+                "SPIR-V code size must be non-zero multiple of 4, but it is {} bytes",
+                size
+            ),
+            Self::BadMagicNumber(magic) => write!(
+                f,
+                "SPIR-V magic number must be {:#010x}, but it is {:#010x}",
+                SPIRV_MAGIC, magic
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for ShaderModuleError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_reads_little_and_big_endian_words() {
+        let le = [0x03, 0x02, 0x23, 0x07, 0x01, 0x00, 0x00, 0x00];
+        let builder = ShaderModuleBuilder::from_bytes(&le).unwrap();
+        assert_eq!(builder.code, vec![SPIRV_MAGIC, 1]);
+
+        let be = [0x07, 0x23, 0x02, 0x03, 0x00, 0x00, 0x00, 0x01];
+        let builder = ShaderModuleBuilder::from_bytes(&be).unwrap();
+        assert_eq!(builder.code, vec![SPIRV_MAGIC, 1]);
+    }
+
+    #[test]
+    fn from_bytes_rejects_truncated_code() {
+        let truncated = [0x03, 0x02, 0x23, 0x07, 0x01, 0x00];
+        match ShaderModuleBuilder::from_bytes(&truncated) {
+            Err(ShaderModuleError::BadCodeSize(6)) => {}
+            _ => panic!("truncated code must be rejected"),
+        }
+
+        match ShaderModuleBuilder::from_bytes(&[]) {
+            Err(ShaderModuleError::BadCodeSize(0)) => {}
+            _ => panic!("empty code must be rejected"),
+        }
+    }
+
+    #[test]
+    fn from_bytes_rejects_wrong_magic() {
+        let bytes = [0xde, 0xad, 0xbe, 0xef];
+        match ShaderModuleBuilder::from_bytes(&bytes) {
+            Err(ShaderModuleError::BadMagicNumber(0xefbe_adde)) => {}
+            _ => panic!("wrong magic number must be rejected"),
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use ash::vk;
+
+/// Builds `vk::ImageSubresourceRange`. Presets cover all mip levels and array layers.
+#[derive(Debug, Copy, Clone)]
+pub struct SubresourceRange {
+    range: vk::ImageSubresourceRange,
+}
+
+impl SubresourceRange {
+    pub fn all(aspect_mask: vk::ImageAspectFlags) -> Self {
+        Self {
+            range: vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: vk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: vk::REMAINING_ARRAY_LAYERS,
+            },
+        }
+    }
+
+    pub fn color_all() -> Self {
+        Self::all(vk::ImageAspectFlags::COLOR)
+    }
+
+    pub fn depth_all() -> Self {
+        Self::all(vk::ImageAspectFlags::DEPTH)
+    }
+
+    pub fn mips(mut self, base: u32, count: u32) -> Self {
+        self.range.base_mip_level = base;
+        self.range.level_count = count;
+        self
+    }
+
+    pub fn layers(mut self, base: u32, count: u32) -> Self {
+        self.range.base_array_layer = base;
+        self.range.layer_count = count;
+        self
+    }
+
+    pub fn raw(&self) -> vk::ImageSubresourceRange {
+        self.range
+    }
+}
+
+impl From<SubresourceRange> for vk::ImageSubresourceRange {
+    fn from(range: SubresourceRange) -> Self {
+        range.raw()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(range: vk::ImageSubresourceRange) -> (vk::ImageAspectFlags, u32, u32, u32, u32) {
+        (
+            range.aspect_mask,
+            range.base_mip_level,
+            range.level_count,
+            range.base_array_layer,
+            range.layer_count,
+        )
+    }
+
+    #[test]
+    fn presets_cover_whole_image() {
+        let (mips, layers) = (vk::REMAINING_MIP_LEVELS, vk::REMAINING_ARRAY_LAYERS);
+        let color = fields(SubresourceRange::color_all().raw());
+        assert_eq!(color, (vk::ImageAspectFlags::COLOR, 0, mips, 0, layers));
+
+        let depth = fields(SubresourceRange::depth_all().into());
+        assert_eq!(depth, (vk::ImageAspectFlags::DEPTH, 0, mips, 0, layers));
+    }
+
+    #[test]
+    fn mips_and_layers_are_customized() {
+        let range = SubresourceRange::color_all().mips(2, 3).layers(1, 4).raw();
+        assert_eq!(fields(range), (vk::ImageAspectFlags::COLOR, 2, 3, 1, 4));
+    }
+}
@@ -0,0 +1,452 @@
+use crate::instance::Instance;
+use ash::extensions::khr;
+use ash::prelude::VkResult;
+use ash::vk;
+use raw_window_handle::RawWindowHandle;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Creates `VK_KHR_surface` for window.
+///
+/// Instance must be built with `VK_KHR_surface` and the platform surface extension
+/// (e.g. `VK_KHR_xlib_surface`, `VK_KHR_wayland_surface` or `VK_KHR_win32_surface`) enabled,
+/// otherwise surface creation fails.
+pub struct SurfaceBuilder {
+    window_handle: RawWindowHandle,
+}
+
+impl SurfaceBuilder {
+    pub fn new(window_handle: RawWindowHandle) -> Self {
+        Self { window_handle }
+    }
+
+    /// # Safety
+    /// `window_handle` must describe valid window, that outlives created surface.
+    pub unsafe fn build(self, instance: Instance) -> CreateSurfaceResult<Surface> {
+        Surface::new(instance, &self.window_handle)
+    }
+}
+
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct Surface {
+    unique_surface: Arc<UniqueSurface>,
+}
+
+impl Surface {
+    /// # Safety
+    /// `window_handle` must describe valid window, that outlives the surface.
+    pub unsafe fn new(
+        instance: Instance,
+        window_handle: &RawWindowHandle,
+    ) -> CreateSurfaceResult<Self> {
+        UniqueSurface::new(instance, window_handle).map(|us| Self {
+            unique_surface: Arc::new(us),
+        })
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn handle(&self) -> &vk::SurfaceKHR {
+        self.unique_surface.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_surface)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_surface, &other.unique_surface)
+    }
+
+    pub fn instance(&self) -> &Instance {
+        self.unique_surface.instance()
+    }
+
+    /// # Safety
+    /// `pdevice` must be enumerated from the instance, this surface was created with.
+    pub unsafe fn capabilities(
+        &self,
+        pdevice: vk::PhysicalDevice,
+    ) -> VkResult<vk::SurfaceCapabilitiesKHR> {
+        self.unique_surface
+            .loader()
+            .get_physical_device_surface_capabilities(pdevice, *self.handle())
+    }
+
+    /// # Safety
+    /// `pdevice` must be enumerated from the instance, this surface was created with.
+    pub unsafe fn formats(
+        &self,
+        pdevice: vk::PhysicalDevice,
+    ) -> VkResult<Vec<vk::SurfaceFormatKHR>> {
+        self.unique_surface
+            .loader()
+            .get_physical_device_surface_formats(pdevice, *self.handle())
+    }
+
+    /// # Safety
+    /// `pdevice` must be enumerated from the instance, this surface was created with.
+    pub unsafe fn present_modes(
+        &self,
+        pdevice: vk::PhysicalDevice,
+    ) -> VkResult<Vec<vk::PresentModeKHR>> {
+        self.unique_surface
+            .loader()
+            .get_physical_device_surface_present_modes(pdevice, *self.handle())
+    }
+
+    /// Returns first of `preferred` formats, supported by surface, or first supported format.
+    ///
+    /// # Safety
+    /// `pdevice` must be enumerated from the instance, this surface was created with.
+    pub unsafe fn choose_format(
+        &self,
+        pdevice: vk::PhysicalDevice,
+        preferred: &[vk::SurfaceFormatKHR],
+    ) -> VkResult<vk::SurfaceFormatKHR> {
+        let available = self.formats(pdevice)?;
+        choose_format(&available, preferred).ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)
+    }
+
+    /// Returns first of `preferred` present modes, supported by surface.
+    /// Else returns `fallback` if it's supported, or `FIFO`, which is always supported.
+    ///
+    /// # Safety
+    /// `pdevice` must be enumerated from the instance, this surface was created with.
+    pub unsafe fn choose_present_mode(
+        &self,
+        pdevice: vk::PhysicalDevice,
+        preferred: &[vk::PresentModeKHR],
+        fallback: vk::PresentModeKHR,
+    ) -> VkResult<vk::PresentModeKHR> {
+        let available = self.present_modes(pdevice)?;
+        Ok(choose_present_mode(&available, preferred, fallback))
+    }
+}
+
+fn choose_format(
+    available: &[vk::SurfaceFormatKHR],
+    preferred: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+    // Single undefined format means, that surface has no preferred format.
+    if let [only] = available {
+        if only.format == vk::Format::UNDEFINED {
+            return preferred.first().copied().or(Some(*only));
+        }
+    }
+
+    preferred
+        .iter()
+        .find(|p| available.contains(p))
+        .or_else(|| available.first())
+        .copied()
+}
+
+fn choose_present_mode(
+    available: &[vk::PresentModeKHR],
+    preferred: &[vk::PresentModeKHR],
+    fallback: vk::PresentModeKHR,
+) -> vk::PresentModeKHR {
+    preferred
+        .iter()
+        .copied()
+        .find(|p| available.contains(p))
+        .unwrap_or(if available.contains(&fallback) {
+            fallback
+        } else {
+            vk::PresentModeKHR::FIFO
+        })
+}
+
+struct UniqueSurface {
+    instance: Instance,
+    loader: khr::Surface,
+    handle: vk::SurfaceKHR,
+}
+
+impl UniqueSurface {
+    pub unsafe fn new(
+        instance: Instance,
+        window_handle: &RawWindowHandle,
+    ) -> CreateSurfaceResult<Self> {
+        log::trace!(target: crate::LOG_TARGET, "Creating vk surface");
+        let handle = create_raw_surface(&instance, window_handle)?;
+        let loader = khr::Surface::new(instance.entry(), instance.handle());
+        Ok(Self {
+            instance,
+            loader,
+            handle,
+        })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::SurfaceKHR {
+        &self.handle
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    pub fn loader(&self) -> &khr::Surface {
+        &self.loader
+    }
+}
+
+impl Drop for UniqueSurface {
+    fn drop(&mut self) {
+        log::trace!(target: crate::LOG_TARGET, "Destroying vk surface");
+        unsafe {
+            self.loader
+                .destroy_surface(self.handle, self.instance.allocation_callbacks())
+        }
+    }
+}
+
+impl Eq for UniqueSurface {}
+
+impl PartialEq for UniqueSurface {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueSurface {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+/// Returns instance extensions, required to create surface for `window_handle`:
+/// `VK_KHR_surface` and platform surface extension.
+/// For unsupported platforms only `VK_KHR_surface` is returned.
+pub fn required_extensions(window_handle: &RawWindowHandle) -> Vec<&'static CStr> {
+    let platform_extension = match window_handle {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Windows(_) => Some(khr::Win32Surface::name()),
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Wayland(_) => Some(khr::WaylandSurface::name()),
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Xlib(_) => Some(khr::XlibSurface::name()),
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Xcb(_) => Some(khr::XcbSurface::name()),
+
+        #[cfg(target_os = "android")]
+        RawWindowHandle::Android(_) => Some(khr::AndroidSurface::name()),
+
+        _ => None,
+    };
+
+    let mut extensions = vec![khr::Surface::name()];
+    extensions.extend(platform_extension);
+    extensions
+}
+
+unsafe fn create_raw_surface(
+    instance: &Instance,
+    window_handle: &RawWindowHandle,
+) -> CreateSurfaceResult<vk::SurfaceKHR> {
+    let entry = instance.entry();
+    let raw_instance = instance.handle();
+
+    match window_handle {
+        #[cfg(target_os = "windows")]
+        RawWindowHandle::Windows(handle) => {
+            let create_info = vk::Win32SurfaceCreateInfoKHR {
+                hinstance: handle.hinstance,
+                hwnd: handle.hwnd,
+                ..Default::default()
+            };
+            let loader = khr::Win32Surface::new(entry, raw_instance);
+            Ok(loader.create_win32_surface(&create_info, instance.allocation_callbacks())?)
+        }
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Wayland(handle) => {
+            let create_info = vk::WaylandSurfaceCreateInfoKHR {
+                display: handle.display,
+                surface: handle.surface,
+                ..Default::default()
+            };
+            let loader = khr::WaylandSurface::new(entry, raw_instance);
+            Ok(loader.create_wayland_surface(&create_info, instance.allocation_callbacks())?)
+        }
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Xlib(handle) => {
+            let create_info = vk::XlibSurfaceCreateInfoKHR {
+                dpy: handle.display as *mut vk::Display,
+                window: handle.window,
+                ..Default::default()
+            };
+            let loader = khr::XlibSurface::new(entry, raw_instance);
+            Ok(loader.create_xlib_surface(&create_info, instance.allocation_callbacks())?)
+        }
+
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd"
+        ))]
+        RawWindowHandle::Xcb(handle) => {
+            let create_info = vk::XcbSurfaceCreateInfoKHR {
+                connection: handle.connection,
+                window: handle.window,
+                ..Default::default()
+            };
+            let loader = khr::XcbSurface::new(entry, raw_instance);
+            Ok(loader.create_xcb_surface(&create_info, instance.allocation_callbacks())?)
+        }
+
+        #[cfg(target_os = "android")]
+        RawWindowHandle::Android(handle) => {
+            let create_info = vk::AndroidSurfaceCreateInfoKHR {
+                window: handle.a_native_window,
+                ..Default::default()
+            };
+            let loader = khr::AndroidSurface::new(entry, raw_instance);
+            Ok(loader.create_android_surface(&create_info, instance.allocation_callbacks())?)
+        }
+
+        _ => Err(CreateSurfaceError::UnsupportedPlatform),
+    }
+}
+
+pub type CreateSurfaceResult<T> = Result<T, CreateSurfaceError>;
+
+#[derive(Debug)]
+pub enum CreateSurfaceError {
+    VkError(vk::Result),
+    UnsupportedPlatform,
+}
+
+impl Error for CreateSurfaceError {}
+
+impl fmt::Display for CreateSurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::VkError(e) => write!(
+                f,
+                "Can't create vk surface: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+            Self::UnsupportedPlatform => {
+                write!(f, "Can't create vk surface: unsupported window handle")
+            }
+        }
+    }
+}
+
+impl From<vk::Result> for CreateSurfaceError {
+    fn from(e: vk::Result) -> Self {
+        Self::VkError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(format: vk::Format) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        }
+    }
+
+    #[test]
+    fn undefined_format_allows_preferred() {
+        let available = [format(vk::Format::UNDEFINED)];
+        let preferred = [format(vk::Format::B8G8R8A8_SRGB)];
+        assert_eq!(choose_format(&available, &preferred), Some(preferred[0]));
+    }
+
+    #[test]
+    fn preferred_format_is_chosen_if_available() {
+        let available = [
+            format(vk::Format::B8G8R8A8_UNORM),
+            format(vk::Format::B8G8R8A8_SRGB),
+        ];
+        let preferred = [
+            format(vk::Format::R8G8B8A8_SRGB),
+            format(vk::Format::B8G8R8A8_SRGB),
+        ];
+        assert_eq!(choose_format(&available, &preferred), Some(available[1]));
+    }
+
+    #[test]
+    fn first_available_format_is_fallback() {
+        let available = [
+            format(vk::Format::B8G8R8A8_UNORM),
+            format(vk::Format::B8G8R8A8_SRGB),
+        ];
+        let preferred = [format(vk::Format::R16G16B16A16_SFLOAT)];
+        assert_eq!(choose_format(&available, &preferred), Some(available[0]));
+        assert_eq!(choose_format(&[], &preferred), None);
+    }
+
+    #[test]
+    fn present_mode_falls_back_to_fifo() {
+        let mailbox = [vk::PresentModeKHR::MAILBOX];
+        let immediate = vk::PresentModeKHR::IMMEDIATE;
+
+        let with_mailbox = [vk::PresentModeKHR::FIFO, vk::PresentModeKHR::MAILBOX];
+        assert_eq!(
+            choose_present_mode(&with_mailbox, &mailbox, immediate),
+            vk::PresentModeKHR::MAILBOX
+        );
+
+        let fifo_only = [vk::PresentModeKHR::FIFO];
+        assert_eq!(
+            choose_present_mode(&fifo_only, &mailbox, immediate),
+            vk::PresentModeKHR::FIFO
+        );
+
+        let with_immediate = [vk::PresentModeKHR::FIFO, immediate];
+        assert_eq!(
+            choose_present_mode(&with_immediate, &mailbox, immediate),
+            immediate
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use crate::device::Device;
+use crate::surface::Surface;
+use ash::extensions::khr;
+use ash::version::DeviceV1_0;
+use ash::vk;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// Device must be created with `VK_KHR_swapchain` extension enabled.
+pub struct SwapchainBuilder {
+    format: vk::SurfaceFormatKHR,
+    extent: vk::Extent2D,
+    present_mode: vk::PresentModeKHR,
+    image_count: u32,
+    usage: vk::ImageUsageFlags,
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
+}
+
+impl SwapchainBuilder {
+    pub fn new(
+        format: vk::SurfaceFormatKHR,
+        extent: vk::Extent2D,
+        present_mode: vk::PresentModeKHR,
+        image_count: u32,
+    ) -> Self {
+        Self {
+            format,
+            extent,
+            present_mode,
+            image_count,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            pre_transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
+            composite_alpha: vk::CompositeAlphaFlagsKHR::OPAQUE,
+        }
+    }
+
+    pub fn with_usage(mut self, usage: vk::ImageUsageFlags) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_pre_transform(mut self, pre_transform: vk::SurfaceTransformFlagsKHR) -> Self {
+        self.pre_transform = pre_transform;
+        self
+    }
+
+    pub fn with_composite_alpha(mut self, composite_alpha: vk::CompositeAlphaFlagsKHR) -> Self {
+        self.composite_alpha = composite_alpha;
+        self
+    }
+
+    pub fn build(self, surface: Surface, device: Device) -> SwapchainResult<Swapchain> {
+        let create_info = vk::SwapchainCreateInfoKHR {
+            surface: unsafe { *surface.handle() },
+            min_image_count: self.image_count,
+            image_format: self.format.format,
+            image_color_space: self.format.color_space,
+            image_extent: self.extent,
+            image_array_layers: 1,
+            image_usage: self.usage,
+            image_sharing_mode: vk::SharingMode::EXCLUSIVE,
+            pre_transform: self.pre_transform,
+            composite_alpha: self.composite_alpha,
+            present_mode: self.present_mode,
+            clipped: vk::TRUE,
+            ..Default::default()
+        };
+
+        unsafe { Swapchain::new(surface, device, &create_info) }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Swapchain {
+    unique_swapchain: Arc<UniqueSwapchain>,
+}
+
+impl Swapchain {
+    /// # Safety
+    /// `create_info.surface` must be handle of `surface`.
+    pub unsafe fn new(
+        surface: Surface,
+        device: Device,
+        create_info: &vk::SwapchainCreateInfoKHR,
+    ) -> SwapchainResult<Self> {
+        UniqueSwapchain::new(surface, device, create_info).map(|us| Self {
+            unique_swapchain: Arc::new(us),
+        })
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn handle(&self) -> &vk::SwapchainKHR {
+        self.unique_swapchain.handle()
+    }
+
+    /// Number of alive clones of this handle.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.unique_swapchain)
+    }
+
+    /// Returns `true` if `other` is clone of this handle.
+    /// Unlike `==`, doesn't compare raw handles, which can be reused after destruction.
+    pub fn same_instance(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.unique_swapchain, &other.unique_swapchain)
+    }
+
+    /// # Safety
+    /// TODO
+    pub unsafe fn images(&self) -> &[vk::Image] {
+        self.unique_swapchain.images()
+    }
+
+    pub fn surface(&self) -> &Surface {
+        self.unique_swapchain.surface()
+    }
+
+    pub fn device(&self) -> &Device {
+        self.unique_swapchain.device()
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.unique_swapchain.format()
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.unique_swapchain.extent()
+    }
+
+    /// Returns index of acquired image and `true` if swapchain is suboptimal for the surface.
+    /// `semaphore` and `fence` can be null handles, but not both.
+    ///
+    /// # Safety
+    /// `semaphore` and `fence` must be valid handles created from swapchain's device or null.
+    pub unsafe fn acquire_next_image(
+        &self,
+        timeout: u64,
+        semaphore: vk::Semaphore,
+        fence: vk::Fence,
+    ) -> SwapchainResult<(u32, bool)> {
+        let result = self.unique_swapchain.loader().acquire_next_image(
+            *self.handle(),
+            timeout,
+            semaphore,
+            fence,
+        );
+        Ok(self.device().track_lost(result)?)
+    }
+
+    /// Recreates swapchain with `new_extent`, e.g. after window resize or `OutOfDate` error.
+    /// Old swapchain is passed as `old_swapchain` and retired.
+    ///
+    /// Waits for device idle, so old swapchain images are not in use.
+    /// Old swapchain is destroyed when all its clones are dropped.
+    pub fn recreate(&mut self, new_extent: vk::Extent2D) -> SwapchainResult<()> {
+        unsafe {
+            let idle = self.device().handle().device_wait_idle();
+            self.device().track_lost(idle)?;
+
+            let old = &self.unique_swapchain;
+            let mut create_info = old.params.create_info(*self.surface().handle());
+            create_info.image_extent = new_extent;
+            create_info.old_swapchain = *old.handle();
+
+            let recreated =
+                UniqueSwapchain::new(self.surface().clone(), self.device().clone(), &create_info)?;
+            self.unique_swapchain = Arc::new(recreated);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn loader(&self) -> &khr::Swapchain {
+        self.unique_swapchain.loader()
+    }
+}
+
+/// Creation parameters, reused on swapchain recreation.
+struct SwapchainParams {
+    min_image_count: u32,
+    format: vk::SurfaceFormatKHR,
+    extent: vk::Extent2D,
+    array_layers: u32,
+    usage: vk::ImageUsageFlags,
+    sharing_mode: vk::SharingMode,
+    queue_family_indices: Vec<u32>,
+    pre_transform: vk::SurfaceTransformFlagsKHR,
+    composite_alpha: vk::CompositeAlphaFlagsKHR,
+    present_mode: vk::PresentModeKHR,
+    clipped: vk::Bool32,
+}
+
+impl SwapchainParams {
+    unsafe fn from_create_info(create_info: &vk::SwapchainCreateInfoKHR) -> Self {
+        let queue_family_indices = if create_info.queue_family_index_count == 0 {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(
+                create_info.p_queue_family_indices,
+                create_info.queue_family_index_count as usize,
+            )
+            .to_vec()
+        };
+
+        Self {
+            min_image_count: create_info.min_image_count,
+            format: vk::SurfaceFormatKHR {
+                format: create_info.image_format,
+                color_space: create_info.image_color_space,
+            },
+            extent: create_info.image_extent,
+            array_layers: create_info.image_array_layers,
+            usage: create_info.image_usage,
+            sharing_mode: create_info.image_sharing_mode,
+            queue_family_indices,
+            pre_transform: create_info.pre_transform,
+            composite_alpha: create_info.composite_alpha,
+            present_mode: create_info.present_mode,
+            clipped: create_info.clipped,
+        }
+    }
+
+    /// Returned value borrows `queue_family_indices`.
+    fn create_info(&self, surface: vk::SurfaceKHR) -> vk::SwapchainCreateInfoKHR {
+        vk::SwapchainCreateInfoKHR {
+            surface,
+            min_image_count: self.min_image_count,
+            image_format: self.format.format,
+            image_color_space: self.format.color_space,
+            image_extent: self.extent,
+            image_array_layers: self.array_layers,
+            image_usage: self.usage,
+            image_sharing_mode: self.sharing_mode,
+            queue_family_index_count: self.queue_family_indices.len() as u32,
+            p_queue_family_indices: self.queue_family_indices.as_ptr(),
+            pre_transform: self.pre_transform,
+            composite_alpha: self.composite_alpha,
+            present_mode: self.present_mode,
+            clipped: self.clipped,
+            ..Default::default()
+        }
+    }
+}
+
+struct UniqueSwapchain {
+    handle: vk::SwapchainKHR,
+    images: Vec<vk::Image>,
+    loader: khr::Swapchain,
+    params: SwapchainParams,
+    device: Device,
+    surface: Surface,
+}
+
+impl UniqueSwapchain {
+    pub unsafe fn new(
+        surface: Surface,
+        device: Device,
+        create_info: &vk::SwapchainCreateInfoKHR,
+    ) -> SwapchainResult<Self> {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Creating swapchain with format: {:?}, extent: {}x{} and {} images",
+            create_info.image_format,
+            create_info.image_extent.width,
+            create_info.image_extent.height,
+            create_info.min_image_count
+        );
+
+        let loader = khr::Swapchain::new(device.instance().handle(), device.handle());
+        let handle = loader.create_swapchain(create_info, device.allocation_callbacks())?;
+        let images = match loader.get_swapchain_images(handle) {
+            Ok(images) => images,
+            Err(e) => {
+                loader.destroy_swapchain(handle, device.allocation_callbacks());
+                return Err(e.into());
+            }
+        };
+
+        Ok(Self {
+            handle,
+            images,
+            loader,
+            params: SwapchainParams::from_create_info(create_info),
+            device,
+            surface,
+        })
+    }
+
+    pub unsafe fn handle(&self) -> &vk::SwapchainKHR {
+        &self.handle
+    }
+
+    pub unsafe fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    pub fn loader(&self) -> &khr::Swapchain {
+        &self.loader
+    }
+
+    pub fn surface(&self) -> &Surface {
+        &self.surface
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.params.format.format
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.params.extent
+    }
+}
+
+impl Drop for UniqueSwapchain {
+    fn drop(&mut self) {
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Destroying swapchain with {} images of format: {:?}",
+            self.images.len(),
+            self.format()
+        );
+        unsafe {
+            self.loader
+                .destroy_swapchain(self.handle, self.device.allocation_callbacks())
+        }
+    }
+}
+
+impl fmt::Debug for UniqueSwapchain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Swapchain")
+            .field("handle", &self.handle)
+            .finish()
+    }
+}
+
+impl Eq for UniqueSwapchain {}
+
+impl PartialEq for UniqueSwapchain {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { self.handle() == other.handle() }
+    }
+}
+
+impl Hash for UniqueSwapchain {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+pub type SwapchainResult<T> = Result<T, SwapchainError>;
+
+#[derive(Debug)]
+pub enum SwapchainError {
+    /// Swapchain doesn't match the surface anymore and must be recreated.
+    OutOfDate,
+    /// Device is lost and must be recreated with all its objects.
+    DeviceLost,
+    VkError(vk::Result),
+}
+
+impl Error for SwapchainError {}
+
+impl fmt::Display for SwapchainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OutOfDate => write!(f, "Swapchain is out of date"),
+            Self::DeviceLost => write!(f, "Swapchain operation failed: device lost"),
+            Self::VkError(e) => write!(
+                f,
+                "Swapchain operation failed: {} ({})",
+                e,
+                crate::describe_result(*e)
+            ),
+        }
+    }
+}
+
+impl From<vk::Result> for SwapchainError {
+    fn from(e: vk::Result) -> Self {
+        match e {
+            vk::Result::ERROR_OUT_OF_DATE_KHR => Self::OutOfDate,
+            vk::Result::ERROR_DEVICE_LOST => Self::DeviceLost,
+            e => Self::VkError(e),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::device::Device;
+use crate::fence::{CreateFenceError, Fence, FenceBuilder};
+use crate::semaphore::{CreateSemaphoreError, Semaphore, SemaphoreBuilder};
+use ash::prelude::VkResult;
+use ash::vk;
+use std::error::Error;
+use std::fmt;
+
+/// Per-frame synchronization objects for rendering several frames in flight.
+pub struct FramesInFlight {
+    frames: Vec<FrameContext>,
+    current: usize,
+}
+
+impl FramesInFlight {
+    /// Creates sync objects for `frame_count` frames. In-flight fences are created signaled,
+    /// so first `begin_frame` call for each frame doesn't block.
+    pub fn new(device: Device, frame_count: usize) -> FramesInFlightResult<Self> {
+        if frame_count == 0 {
+            return Err(FramesInFlightError::ZeroFrameCount);
+        }
+        log::trace!(
+            target: crate::LOG_TARGET,
+            "Creating sync objects for {} frames in flight",
+            frame_count
+        );
+
+        let frames = (0..frame_count)
+            .map(|index| {
+                Ok(FrameContext {
+                    index,
+                    image_available: SemaphoreBuilder::default().build(device.clone())?,
+                    render_finished: SemaphoreBuilder::default().build(device.clone())?,
+                    in_flight: FenceBuilder::default()
+                        .with_flags(vk::FenceCreateFlags::SIGNALED)
+                        .build(device.clone())?,
+                })
+            })
+            .collect::<FramesInFlightResult<_>>()?;
+
+        Ok(Self { frames, current: 0 })
+    }
+
+    /// Waits until current frame's previous submission completes and advances to the next frame.
+    /// Fence stays signaled, so frame can be skipped if image acquisition fails.
+    /// Call `FrameContext::reset_fence` after successful acquisition, before submission.
+    pub fn begin_frame(&mut self) -> VkResult<FrameContext> {
+        let frame = self.frames[self.current].clone();
+        frame.in_flight.wait(u64::MAX)?;
+        self.current = (self.current + 1) % self.frames.len();
+        Ok(frame)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Index of frame, returned by next `begin_frame` call.
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+}
+
+/// Sync objects of single frame.
+#[derive(Clone)]
+pub struct FrameContext {
+    pub index: usize,
+    /// To be signaled by swapchain image acquisition.
+    pub image_available: Semaphore,
+    /// To be signaled by frame's rendering submission and waited by presentation.
+    pub render_finished: Semaphore,
+    /// To be signaled by frame's rendering submission.
+    pub in_flight: Fence,
+}
+
+impl FrameContext {
+    /// Resets `in_flight` fence before it's passed to frame's rendering submission.
+    /// If nothing is submitted after reset, next `begin_frame` for this frame blocks forever.
+    pub fn reset_fence(&self) -> VkResult<()> {
+        self.in_flight.reset()
+    }
+}
+
+pub type FramesInFlightResult<T> = Result<T, FramesInFlightError>;
+
+#[derive(Debug)]
+pub enum FramesInFlightError {
+    CreateFenceError(CreateFenceError),
+    CreateSemaphoreError(CreateSemaphoreError),
+    ZeroFrameCount,
+}
+
+impl Error for FramesInFlightError {}
+
+impl fmt::Display for FramesInFlightError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CreateFenceError(e) => write!(f, "Can't create frame sync objects: {}", e),
+            Self::CreateSemaphoreError(e) => {
+                write!(f, "Can't create frame sync objects: {}", e)
+            }
+            Self::ZeroFrameCount => write!(f, "Frames in flight count must be non-zero"),
+        }
+    }
+}
+
+impl From<CreateFenceError> for FramesInFlightError {
+    fn from(e: CreateFenceError) -> Self {
+        Self::CreateFenceError(e)
+    }
+}
+
+impl From<CreateSemaphoreError> for FramesInFlightError {
+    fn from(e: CreateSemaphoreError) -> Self {
+        Self::CreateSemaphoreError(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::compute_device;
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn frames_are_cycled() {
+        let (device, queue, _pool) = compute_device();
+        let mut frames = FramesInFlight::new(device, 3).unwrap();
+        assert_eq!(frames.frame_count(), 3);
+
+        for i in 0..7 {
+            assert_eq!(frames.current_index(), i % 3);
+            let frame = frames.begin_frame().unwrap();
+            assert_eq!(frame.index, i % 3);
+
+            // Every other frame imitates failed image acquisition and submits nothing.
+            if i % 2 == 0 {
+                frame.reset_fence().unwrap();
+                unsafe { queue.submit_batch(&[], Some(&frame.in_flight)).unwrap() };
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "requires Vulkan device"]
+    fn zero_frames_is_error() {
+        let (device, _queue, _pool) = compute_device();
+        match FramesInFlight::new(device, 0) {
+            Err(FramesInFlightError::ZeroFrameCount) => {}
+            _ => panic!("zero frame count must be rejected"),
+        }
+    }
+}
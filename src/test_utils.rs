@@ -0,0 +1,40 @@
+use crate::command_pool::{CommandPool, CommandPoolBuilder};
+use crate::device::pdevice_selectors::{self, PhysicalDeviceSelector};
+use crate::device::{Device, DeviceBuilder};
+use crate::instance::InstanceBuilder;
+use crate::queue::Queue;
+
+/// Creates device with single compute queue and command pool for it.
+/// Panics if Vulkan isn't available, so tests using it are ignored by default.
+pub fn compute_device() -> (Device, Queue, CommandPool) {
+    compute_device_with(|builder| builder)
+}
+
+/// Like `compute_device`, but lets `configure` device builder, e.g. to enable features.
+pub fn compute_device_with(
+    configure: impl FnOnce(DeviceBuilder) -> DeviceBuilder,
+) -> (Device, Queue, CommandPool) {
+    device_with(Box::new(pdevice_selectors::any_compute), configure)
+}
+
+/// Like `compute_device`, but device is selected by `selector`.
+pub fn device_with(
+    selector: Box<dyn PhysicalDeviceSelector>,
+    configure: impl FnOnce(DeviceBuilder) -> DeviceBuilder,
+) -> (Device, Queue, CommandPool) {
+    let entry = ash::Entry::new().expect("Vulkan loader must be available");
+    let instance = InstanceBuilder::new(entry)
+        .with_api_version_clamped(1, 2, 0)
+        .build()
+        .expect("Instance must be created");
+    let device = configure(DeviceBuilder::new(selector))
+        .build(instance)
+        .expect("Device must be created");
+
+    let family_index = device.queues_info()[0].family_index;
+    let queue = Queue::get(device.clone(), family_index, 0).expect("Queue must exist");
+    let pool = CommandPoolBuilder::new(family_index)
+        .build(device.clone())
+        .expect("Command pool must be created");
+    (device, queue, pool)
+}